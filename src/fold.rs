@@ -0,0 +1,413 @@
+use crate::{BinOp, ColumnType, Function, INTEGER_RANGE, Output, UnOp, Value};
+
+use std::{cmp::Ordering, collections::HashMap};
+
+use proc_macro2::Ident;
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Boolean(left), Value::Boolean(right)) => left.partial_cmp(right),
+            (Value::Integer(left), Value::Integer(right)) => left.partial_cmp(right),
+            (Value::Real(left), Value::Real(right)) => left.partial_cmp(right),
+            (Value::String(left), Value::String(right)) => left.partial_cmp(right),
+            _ => None,
+        }
+    }
+}
+
+/// Only finite reals can be written as literals in the generated code.
+fn finite(value: f64) -> Option<Value> {
+    value.is_finite().then_some(Value::Real(value))
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn binary(left: &Value, operator: BinOp, right: &Value) -> Option<Value> {
+    let (left, right) = match (left, right) {
+        (Value::Integer(left), Value::Real(_)) => (&Value::Real(*left as f64), right),
+        (Value::Real(_), Value::Integer(right)) => (left, &Value::Real(*right as f64)),
+        _ => (left, right),
+    };
+
+    if operator.is_comparison() {
+        let ordering = left.partial_cmp(right)?;
+        let result = match operator {
+            BinOp::Eq => ordering.is_eq(),
+            BinOp::Ne => ordering.is_ne(),
+            BinOp::Gt => ordering.is_gt(),
+            BinOp::Ge => ordering.is_ge(),
+            BinOp::Lt => ordering.is_lt(),
+            BinOp::Le => ordering.is_le(),
+            _ => unreachable!(),
+        };
+        return Some(Value::Boolean(result));
+    }
+
+    match (left, right) {
+        (Value::Integer(left), Value::Integer(right)) => match operator {
+            BinOp::Add => left.checked_add(*right),
+            BinOp::Sub => left.checked_sub(*right),
+            BinOp::Mul => left.checked_mul(*right),
+            BinOp::Div => left.checked_div(*right),
+            BinOp::Mod => left.checked_rem(*right),
+            _ => None,
+        }
+        .map(Value::Integer),
+        (Value::Real(left), Value::Real(right)) => finite(match operator {
+            BinOp::Add => left + right,
+            BinOp::Sub => left - right,
+            BinOp::Mul => left * right,
+            BinOp::Div => left / right,
+            BinOp::Mod => left % right,
+            _ => None?,
+        }),
+        _ => None,
+    }
+}
+
+fn unary(operator: UnOp, right: &Value) -> Option<Value> {
+    match (operator, right) {
+        (UnOp::Negate, Value::Integer(i)) => i.checked_neg().map(Value::Integer),
+        (UnOp::Negate, Value::Real(r)) => Some(Value::Real(-r)),
+        (UnOp::Not, Value::Boolean(b)) => Some(Value::Boolean(!b)),
+        _ => None,
+    }
+}
+
+/// Evaluates `function`, which must only have literal arguments, in the same way as the
+/// generated code.
+#[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+fn function(function: &Function) -> Option<Value> {
+    let args: Vec<&Value> = function
+        .args()
+        .into_iter()
+        .map(Output::literal)
+        .collect::<Option<_>>()?;
+
+    match (function, args.as_slice()) {
+        (Function::Boolean(_), [value]) => Some(Value::Boolean(match value {
+            Value::Boolean(b) => *b,
+            Value::Integer(i) => *i != 0,
+            Value::Real(r) => *r != 0.0,
+            Value::String(s) => !s.is_empty(),
+        })),
+        (Function::Integer(_), [value]) => match value {
+            Value::Boolean(b) => Some(Value::Integer(i64::from(*b))),
+            Value::Integer(i) => Some(Value::Integer(*i)),
+            Value::Real(r) => {
+                let floor = r.floor();
+                INTEGER_RANGE
+                    .contains(&floor)
+                    .then_some(Value::Integer(floor as i64))
+            }
+            Value::String(s) => s.parse().ok().map(Value::Integer),
+        },
+        (Function::Real(_), [value]) => match value {
+            Value::Boolean(b) => Some(Value::Real(f64::from(u8::from(*b)))),
+            Value::Integer(i) => Some(Value::Real(*i as f64)),
+            Value::Real(r) => Some(Value::Real(*r)),
+            Value::String(s) => finite(s.parse().ok()?),
+        },
+        (Function::String(_), [value]) => Some(Value::String(match value {
+            Value::Boolean(b) => b.to_string(),
+            Value::Integer(i) => i.to_string(),
+            Value::Real(r) => r.to_string(),
+            Value::String(s) => s.to_owned(),
+        })),
+        (Function::Ceiling(_), [Value::Real(r)]) => Some(Value::Real(r.ceil())),
+        (Function::Floor(_), [Value::Real(r)]) => Some(Value::Real(r.floor())),
+        (Function::Round(_), [Value::Real(r)]) => Some(Value::Real(r.round())),
+        (Function::Concat(_, _), [Value::String(left), Value::String(right)]) => {
+            Some(Value::String(format!("{left}{right}")))
+        }
+        (Function::Coalesce(_) | Function::Default(_, _), [value, ..]) => Some((*value).clone()),
+        _ => None,
+    }
+}
+
+impl Output {
+    /// Gets the value of this expression if it is a literal.
+    const fn literal(&self) -> Option<&Value> {
+        if let Output::Literal(value) = self {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    /// Replaces every sub-expression that only contains literals with its value.
+    ///
+    /// Sub-expressions that would return an error when evaluated are left in place, so that
+    /// the error is handled in the same way as any other.
+    pub(crate) fn fold(&mut self) {
+        let value = match self {
+            Output::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                left.fold();
+                right.fold();
+                left.literal()
+                    .zip(right.literal())
+                    .and_then(|(left, right)| binary(left, *operator, right))
+            }
+            Output::Function(inner) => {
+                for arg in inner.args_mut() {
+                    arg.fold();
+                }
+                function(inner)
+            }
+            Output::Unary { operator, right } => {
+                right.fold();
+                right.literal().and_then(|right| unary(*operator, right))
+            }
+            Output::Identifier(_) | Output::Literal(_) => None,
+        };
+
+        if let Some(value) = value {
+            *self = Output::Literal(value);
+        }
+    }
+
+    /// Checks whether this expression gives the same result whenever it is evaluated for the
+    /// same row.
+    ///
+    /// Calls to user functions are excluded, as are `prev` and `next`, which depend on where the
+    /// row is in its section.
+    fn is_deterministic(&self) -> bool {
+        match self {
+            Output::Function(
+                Function::Call(_, _) | Function::User(_, _) | Function::Next(_) | Function::Prev(_),
+            ) => false,
+            Output::Binary { left, right, .. } => {
+                left.is_deterministic() && right.is_deterministic()
+            }
+            Output::Function(function) => function.args().iter().all(|arg| arg.is_deterministic()),
+            Output::Unary { right, .. } => right.is_deterministic(),
+            Output::Identifier(_) | Output::Literal(_) => true,
+        }
+    }
+
+    /// Checks whether `left` and `right` always evaluate to the same value as each other.
+    ///
+    /// Reals are excluded, as NaN is not equal to itself.
+    fn same_operands(left: &Self, right: &Self, var_types: &HashMap<Ident, ColumnType>) -> bool {
+        left == right
+            && left.is_deterministic()
+            && left.return_type(var_types).ok() != Some(ColumnType::Float)
+    }
+
+    /// Finds comparisons in this expression that have the same result for every row, such as
+    /// `value == value`, and describes them.
+    ///
+    /// This must only be called on expressions that have been type checked against `var_types`.
+    pub(crate) fn constant_comparisons(
+        &self,
+        var_types: &HashMap<Ident, ColumnType>,
+    ) -> Vec<String> {
+        let mut comparisons = match self {
+            Output::Binary { left, right, .. } => {
+                let mut comparisons = left.constant_comparisons(var_types);
+                comparisons.extend(right.constant_comparisons(var_types));
+                comparisons
+            }
+            Output::Function(function) => function
+                .args()
+                .iter()
+                .flat_map(|arg| arg.constant_comparisons(var_types))
+                .collect(),
+            Output::Unary { right, .. } => right.constant_comparisons(var_types),
+            Output::Identifier(_) | Output::Literal(_) => vec![],
+        };
+
+        if let Output::Binary {
+            left,
+            operator,
+            right,
+        } = self
+            && operator.is_comparison()
+        {
+            let mut folded = self.clone();
+            folded.fold();
+            let result = if let Output::Literal(Value::Boolean(result)) = folded {
+                Some(result)
+            } else if Self::same_operands(left, right, var_types) {
+                Some(matches!(operator, BinOp::Eq | BinOp::Ge | BinOp::Le))
+            } else {
+                None
+            };
+
+            if let Some(result) = result {
+                comparisons.push(format!("comparison with '{operator}' is always {result}"));
+            }
+        }
+
+        comparisons
+    }
+
+    /// Checks whether this expression has the same result for every row where it does not
+    /// return an error, such as `3`, `value - value` or `1 < 2`.
+    ///
+    /// This must only be called on expressions that have been folded and type checked against
+    /// `var_types`.
+    pub(crate) fn is_constant(&self, var_types: &HashMap<Ident, ColumnType>) -> bool {
+        match self {
+            Output::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                ((operator.is_comparison() || *operator == BinOp::Sub)
+                    && Self::same_operands(left, right, var_types))
+                    || (left.is_constant(var_types) && right.is_constant(var_types))
+            }
+            Output::Function(function) => {
+                self.is_deterministic()
+                    && function.args().iter().all(|arg| arg.is_constant(var_types))
+            }
+            Output::Unary { right, .. } => right.is_constant(var_types),
+            Output::Identifier(_) => false,
+            Output::Literal(_) => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use proc_macro2::Span;
+    use quote::ToTokens;
+
+    fn identifier(name: &str) -> Output {
+        Output::Identifier(Ident::new(name, Span::call_site()))
+    }
+
+    fn folded(mut output: Output) -> Output {
+        output.fold();
+        output
+    }
+
+    #[test]
+    fn integer_overflow_is_not_folded() {
+        let output = Output::Binary {
+            left: Box::new(Output::Literal(Value::Integer(i64::MAX))),
+            operator: BinOp::Add,
+            right: Box::new(Output::Literal(Value::Integer(1))),
+        };
+        assert_eq!(folded(output.clone()), output);
+
+        let output = Output::Unary {
+            operator: UnOp::Negate,
+            right: Box::new(Output::Literal(Value::Integer(i64::MIN))),
+        };
+        assert_eq!(folded(output.clone()), output);
+    }
+
+    #[test]
+    fn division_by_zero_is_not_folded() {
+        for operator in [BinOp::Div, BinOp::Mod] {
+            let output = Output::Binary {
+                left: Box::new(Output::Literal(Value::Integer(1))),
+                operator,
+                right: Box::new(Output::Literal(Value::Integer(0))),
+            };
+            assert_eq!(folded(output.clone()), output);
+        }
+
+        let output = Output::Binary {
+            left: Box::new(Output::Literal(Value::Real(1.0))),
+            operator: BinOp::Div,
+            right: Box::new(Output::Literal(Value::Real(0.0))),
+        };
+        assert_eq!(folded(output.clone()), output);
+    }
+
+    #[test]
+    fn minimum_integer_is_folded() {
+        let output = folded(Output::Binary {
+            left: Box::new(Output::Unary {
+                operator: UnOp::Negate,
+                right: Box::new(Output::Literal(Value::Integer(i64::MAX))),
+            }),
+            operator: BinOp::Sub,
+            right: Box::new(Output::Literal(Value::Integer(1))),
+        });
+        assert_eq!(output, Output::Literal(Value::Integer(i64::MIN)));
+
+        let Output::Literal(value) = output else {
+            unreachable!()
+        };
+        assert_eq!(value.to_token_stream().to_string(), "i64 :: MIN");
+    }
+
+    #[test]
+    fn strings_are_concatenated() {
+        let output = folded(Output::Function(Function::Concat(
+            Box::new(Output::Literal(Value::String("abc".to_string()))),
+            Box::new(Output::Literal(Value::String("def".to_string()))),
+        )));
+        assert_eq!(output, Output::Literal(Value::String("abcdef".to_string())));
+
+        let output = Output::Function(Function::Concat(
+            Box::new(identifier("value")),
+            Box::new(Output::Literal(Value::String("def".to_string()))),
+        ));
+        assert_eq!(folded(output.clone()), output);
+    }
+
+    #[test]
+    fn mixed_arithmetic_is_promoted() {
+        let output = folded(Output::Binary {
+            left: Box::new(Output::Literal(Value::Integer(3))),
+            operator: BinOp::Mul,
+            right: Box::new(Output::Literal(Value::Real(1.5))),
+        });
+        assert_eq!(output, Output::Literal(Value::Real(4.5)));
+    }
+
+    #[test]
+    fn constant_expressions_are_found() {
+        let var_types = HashMap::from([
+            (Ident::new("value", Span::call_site()), ColumnType::Integer),
+            (Ident::new("real", Span::call_site()), ColumnType::Float),
+        ]);
+
+        let difference = Output::Binary {
+            left: Box::new(identifier("value")),
+            operator: BinOp::Sub,
+            right: Box::new(identifier("value")),
+        };
+        assert!(difference.is_constant(&var_types));
+
+        let difference = Output::Binary {
+            left: Box::new(identifier("real")),
+            operator: BinOp::Sub,
+            right: Box::new(identifier("real")),
+        };
+        assert!(!difference.is_constant(&var_types));
+
+        let comparison = folded(Output::Binary {
+            left: Box::new(Output::Literal(Value::Integer(1))),
+            operator: BinOp::Lt,
+            right: Box::new(Output::Literal(Value::Integer(2))),
+        });
+        assert!(comparison.is_constant(&var_types));
+
+        let previous = Box::new(Output::Function(Function::Prev(Ident::new(
+            "value",
+            Span::call_site(),
+        ))));
+        let comparison = Output::Binary {
+            left: previous.clone(),
+            operator: BinOp::Eq,
+            right: previous,
+        };
+        assert!(!comparison.is_constant(&var_types));
+        assert_eq!(
+            comparison.constant_comparisons(&var_types),
+            Vec::<String>::new()
+        );
+    }
+}
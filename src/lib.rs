@@ -1,14 +1,18 @@
-#![feature(proc_macro_expand)]
+#![feature(proc_macro_diagnostic, proc_macro_expand)]
 #![doc = include_str!("../README.md")]
 
+mod fold;
 mod output;
-use output::parse_output;
+mod pattern;
+use output::{is_function_name, parse_name, parse_output};
+use pattern::Pattern;
 mod to_tokens;
 
 use std::{
     collections::{HashMap, VecDeque},
     fmt,
     iter::zip,
+    ops::Range,
 };
 
 extern crate proc_macro;
@@ -16,12 +20,15 @@ extern crate proc_macro;
 use proc_macro2::{Ident, Span, TokenStream};
 use quote::{ToTokens, quote};
 use syn::{
-    Expr, LitStr, Token, parse,
+    Expr, LitStr, Path, Token, parse,
     parse::{Parse, ParseStream},
     parse_macro_input,
 };
 use yaml_rust::{Yaml, YamlLoader, yaml::Hash};
 
+/// The reals that can be converted to an integer without overflow, once they have been rounded.
+const INTEGER_RANGE: Range<f64> = -9_223_372_036_854_775_808.0..9_223_372_036_854_775_808.0;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum ColumnType {
     Bool,
@@ -37,6 +44,17 @@ impl ColumnType {
             ColumnType::Bool | ColumnType::String => false,
         }
     }
+
+    /// Gets the type that values of `self` and `other` can both be promoted to, if any.
+    fn common_type(self, other: ColumnType) -> Option<ColumnType> {
+        if self == other {
+            Some(self)
+        } else if self.is_numeric() && other.is_numeric() {
+            Some(ColumnType::Float)
+        } else {
+            None
+        }
+    }
 }
 
 impl TryFrom<&str> for ColumnType {
@@ -45,7 +63,7 @@ impl TryFrom<&str> for ColumnType {
     fn try_from(value: &str) -> Result<Self, Self::Error> {
         match value {
             "boolean" => Ok(ColumnType::Bool),
-            "float" => Ok(ColumnType::Float),
+            "float" | "real" => Ok(ColumnType::Float),
             "integer" => Ok(ColumnType::Integer),
             "string" => Ok(ColumnType::String),
             _ => Err(format!("invalid column type {value}")),
@@ -97,6 +115,18 @@ enum Value {
     String(String),
 }
 
+impl Value {
+    /// Converts this value to `column_type`, if this can be done implicitly.
+    #[allow(clippy::cast_precision_loss)]
+    fn promote(self, column_type: ColumnType) -> Option<Value> {
+        match (self, column_type) {
+            (Value::Integer(i), ColumnType::Float) => Some(Value::Real(i as f64)),
+            (value, column_type) if ColumnType::from(&value) == column_type => Some(value),
+            _ => None,
+        }
+    }
+}
+
 impl TryFrom<&Yaml> for Value {
     type Error = String;
 
@@ -108,6 +138,7 @@ impl TryFrom<&Yaml> for Value {
                 .map_err(|_| format!("invalid real {value:#?}"))
                 .map(Value::Real),
             Yaml::String(s) => Ok(Value::String(s.to_owned())),
+            Yaml::Boolean(b) => Ok(Value::Boolean(*b)),
             _ => Err(format!("invalid value {value:#?}")),
         }
     }
@@ -126,50 +157,97 @@ enum OnInvalid {
     Abort,
     Average(usize),
     Delete,
+    /// Interpolates linearly across a series of invalid values, using the positions in the
+    /// given column if there is one, or the row numbers otherwise.
+    Interpolate(usize, Option<Ident>),
+    /// Uses whichever valid value is closer, using the positions in the given column if there is
+    /// one, or the row numbers otherwise.
+    Nearest(Option<Ident>),
+    Next,
     Previous(Value),
+    /// Uses a statistic of the last valid values, or the sentinel if there are none.
+    Rolling(Value),
     Sentinel(Value),
 }
 
-fn get_on_invalid(yaml: &Yaml, hash: &mut Hash, kind: &str) -> OnInvalid {
+impl OnInvalid {
+    /// Gets the name of this option if it fills in missing values once the next valid value is
+    /// found.
+    const fn fill_name(&self) -> Option<&'static str> {
+        match self {
+            OnInvalid::Average(_) => Some("average"),
+            OnInvalid::Interpolate(_, _) => Some("interpolate"),
+            OnInvalid::Nearest(_) => Some("nearest"),
+            OnInvalid::Next => Some("next"),
+            _ => None,
+        }
+    }
+
+    /// Gets the column that gives the position of each row, and the key that it was given by.
+    const fn position_column(&self) -> Option<(&Ident, &'static str)> {
+        match self {
+            OnInvalid::Interpolate(_, Some(by)) => Some((by, "interpolate-by")),
+            OnInvalid::Nearest(Some(by)) => Some((by, "nearest-by")),
+            _ => None,
+        }
+    }
+}
+
+fn parse_position_column(hash: &mut Hash, key: &str) -> Option<Ident> {
+    hash.remove(&Yaml::from_str(key)).map(|yaml| {
+        Ident::new(
+            yaml.as_str()
+                .unwrap_or_else(|| panic!("value of '{key}' must be a string")),
+            Span::call_site(),
+        )
+    })
+}
+
+fn get_on_invalid(
+    yaml: &Yaml,
+    hash: &mut Hash,
+    kind: &str,
+    output_type: ColumnType,
+    title: &str,
+    globals: &Globals,
+) -> OnInvalid {
     let on_invalid = yaml
         .as_str()
         .unwrap_or_else(|| panic!("value of on-{kind} must be a string"));
 
     match on_invalid {
         "abort" => OnInvalid::Abort,
-        "average" => {
+        "average" | "interpolate" => {
             let valid_streak = hash
                 .remove(&Yaml::from_str("valid-streak"))
                 .unwrap_or_else(|| {
-                    panic!("'average' option for on-{kind} requires key 'valid-streak'")
+                    panic!("'{on_invalid}' option for on-{kind} requires key 'valid-streak'")
                 })
                 .into_i64()
                 .and_then(|n| n.try_into().ok())
                 .expect("'valid-streak' must be a positive integer");
-            OnInvalid::Average(valid_streak)
+            if on_invalid == "average" {
+                OnInvalid::Average(valid_streak)
+            } else {
+                OnInvalid::Interpolate(valid_streak, parse_position_column(hash, "interpolate-by"))
+            }
         }
         "delete" => OnInvalid::Delete,
-        "previous" => {
+        "nearest" => OnInvalid::Nearest(parse_position_column(hash, "nearest-by")),
+        "next" => OnInvalid::Next,
+        "previous" | "rolling" | "sentinel" => {
             let key = format!("{kind}-sentinel");
-            let sentinel = hash
-                .remove(&Yaml::from_str(&key))
-                .unwrap_or_else(|| {
-                    panic!("'previous' option for on-{kind} requires key '{kind}-sentinel'")
-                })
-                .try_into()
-                .unwrap();
-            OnInvalid::Previous(sentinel)
-        }
-        "sentinel" => {
-            let key = format!("{kind}-sentinel");
-            let sentinel = hash
-                .remove(&Yaml::from_str(&key))
-                .unwrap_or_else(|| {
-                    panic!("'sentinel' option for on-{kind} requires key '{kind}-sentinel'")
-                })
-                .try_into()
-                .unwrap();
-            OnInvalid::Sentinel(sentinel)
+            let yaml = hash.remove(&Yaml::from_str(&key)).unwrap_or_else(|| {
+                panic!("'{on_invalid}' option for on-{kind} requires key '{key}'")
+            });
+            let description = format!("'{key}' in column '{title}'");
+            let sentinel = parse_value(&yaml, output_type, &description, globals)
+                .unwrap_or_else(|| panic!("{description} cannot be null"));
+            match on_invalid {
+                "previous" => OnInvalid::Previous(sentinel),
+                "rolling" => OnInvalid::Rolling(sentinel),
+                _ => OnInvalid::Sentinel(sentinel),
+            }
         }
         _ => panic!("invalid value for on-{kind}: '{on_invalid}'"),
     }
@@ -243,16 +321,34 @@ impl fmt::Display for UnOp {
     }
 }
 
-#[derive(Debug, Clone)]
+/// A Rust function declared in the root `functions` section.
+#[derive(Debug, Clone, PartialEq)]
+struct UserFunction {
+    name: Ident,
+    path: Path,
+    args: Vec<ColumnType>,
+    returns: ColumnType,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 enum Function {
     Boolean(Box<Output>),
+    /// A call to a function that has not yet been matched to a declaration.
+    Call(Ident, Vec<Output>),
     Ceiling(Box<Output>),
+    Coalesce(Vec<Output>),
     Concat(Box<Output>, Box<Output>),
+    Default(Box<Output>, Value),
     Floor(Box<Output>),
+    Format(String, Vec<Output>),
     Integer(Box<Output>),
+    IsNull(Box<Output>),
+    Next(Ident),
+    Prev(Ident),
     Real(Box<Output>),
     Round(Box<Output>),
     String(Box<Output>),
+    User(UserFunction, Vec<Output>),
 }
 
 impl Function {
@@ -265,6 +361,12 @@ impl Function {
             Function::Integer(_) => Ok(ColumnType::Integer),
             Function::Real(_) => Ok(ColumnType::Float),
             Function::String(_) => Ok(ColumnType::String),
+            Function::Next(ident) | Function::Prev(ident) => {
+                let offset_ident = Ident::new(&format!("{self}_{ident}"), Span::call_site());
+                var_types.get(&offset_ident).copied().ok_or_else(|| {
+                    format!("argument to '{self}' must be 'value' or the value of a column")
+                })
+            }
             Function::Ceiling(output) | Function::Floor(output) | Function::Round(output) => {
                 if output.return_type(var_types)? == ColumnType::Float {
                     Ok(ColumnType::Float)
@@ -281,6 +383,115 @@ impl Function {
                     Ok(ColumnType::String)
                 }
             }
+            Function::Coalesce(args) => {
+                let return_type = args[0].return_type(var_types)?;
+                for arg in &args[1..] {
+                    if arg.return_type(var_types)? != return_type {
+                        Err("arguments to 'coalesce' must all have the same type")?;
+                    }
+                }
+                Ok(return_type)
+            }
+            Function::Default(arg, default) => {
+                let return_type = arg.return_type(var_types)?;
+                if default.clone().promote(return_type).is_some() {
+                    Ok(return_type)
+                } else {
+                    Err(format!(
+                        "default value must be {return_type}, found {}",
+                        ColumnType::from(default)
+                    ))
+                }
+            }
+            Function::IsNull(arg) => {
+                if arg.is_reference() {
+                    arg.return_type(var_types)?;
+                    Ok(ColumnType::Bool)
+                } else {
+                    Err("argument to 'is_null' must be a reference to a value".to_string())
+                }
+            }
+            Function::Format(format, args) => {
+                let placeholders = output::format_placeholders(format)?;
+                for (i, (arg, conversion)) in zip(args, placeholders).enumerate() {
+                    let arg_type = arg.return_type(var_types)?;
+                    let supported = match conversion {
+                        "x" | "X" | "o" | "b" => arg_type == ColumnType::Integer,
+                        "e" | "E" => arg_type.is_numeric(),
+                        _ => true,
+                    };
+                    if !supported {
+                        Err(format!(
+                            "argument {} to 'format' is {arg_type}, which cannot be formatted \
+                             with '{{:{conversion}}}'",
+                            i + 2
+                        ))?;
+                    }
+                }
+                Ok(ColumnType::String)
+            }
+            Function::Call(name, _) => Err(format!("unknown function '{name}'")),
+            Function::User(function, args) => {
+                if args.len() != function.args.len() {
+                    Err(format!(
+                        "'{}' takes {} argument(s), found {}",
+                        function.name,
+                        function.args.len(),
+                        args.len()
+                    ))?;
+                }
+                for (i, (arg, expected)) in zip(args, &function.args).enumerate() {
+                    let found = arg.return_type(var_types)?;
+                    if found.common_type(*expected) != Some(*expected) {
+                        Err(format!(
+                            "argument {} to '{}' must be {expected}, found {found}",
+                            i + 1,
+                            function.name
+                        ))?;
+                    }
+                }
+                Ok(function.returns)
+            }
+        }
+    }
+
+    fn args(&self) -> Vec<&Output> {
+        match self {
+            Function::Boolean(arg)
+            | Function::Ceiling(arg)
+            | Function::Floor(arg)
+            | Function::Integer(arg)
+            | Function::Real(arg)
+            | Function::Round(arg)
+            | Function::String(arg)
+            | Function::Default(arg, _)
+            | Function::IsNull(arg) => vec![arg],
+            Function::Call(_, args)
+            | Function::Coalesce(args)
+            | Function::Format(_, args)
+            | Function::User(_, args) => args.iter().collect(),
+            Function::Concat(arg1, arg2) => vec![arg1, arg2],
+            Function::Next(_) | Function::Prev(_) => vec![],
+        }
+    }
+
+    fn args_mut(&mut self) -> Vec<&mut Output> {
+        match self {
+            Function::Boolean(arg)
+            | Function::Ceiling(arg)
+            | Function::Floor(arg)
+            | Function::Integer(arg)
+            | Function::Real(arg)
+            | Function::Round(arg)
+            | Function::String(arg)
+            | Function::Default(arg, _)
+            | Function::IsNull(arg) => vec![arg],
+            Function::Call(_, args)
+            | Function::Coalesce(args)
+            | Function::Format(_, args)
+            | Function::User(_, args) => args.iter_mut().collect(),
+            Function::Concat(arg1, arg2) => vec![arg1, arg2],
+            Function::Next(_) | Function::Prev(_) => vec![],
         }
     }
 }
@@ -288,11 +499,20 @@ impl Function {
 impl fmt::Display for Function {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let string = match self {
+            Function::Call(name, _) | Function::User(UserFunction { name, .. }, _) => {
+                return write!(f, "{name}");
+            }
             Function::Boolean(_) => "boolean",
             Function::Ceiling(_) => "ceiling",
+            Function::Coalesce(_) => "coalesce",
             Function::Concat(_, _) => "concat",
+            Function::Default(_, _) => "default",
             Function::Floor(_) => "floor",
+            Function::Format(_, _) => "format",
             Function::Integer(_) => "integer",
+            Function::IsNull(_) => "is_null",
+            Function::Next(_) => "next",
+            Function::Prev(_) => "prev",
             Function::Real(_) => "real",
             Function::Round(_) => "round",
             Function::String(_) => "string",
@@ -302,7 +522,7 @@ impl fmt::Display for Function {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 enum Output {
     Binary {
         left: Box<Output>,
@@ -332,16 +552,19 @@ impl Output {
                 let left_type = left.return_type(var_types)?;
                 let right_type = right.return_type(var_types)?;
 
-                if left_type != right_type {
-                    Err(format!("cannot compare {left_type} with {right_type}"))?;
-                } else if operator.is_numeric() && !left_type.is_numeric() {
-                    Err(format!("cannot use operator '{operator}' on {left_type}"))?;
+                let Some(operand_type) = left_type.common_type(right_type) else {
+                    Err(format!("cannot compare {left_type} with {right_type}"))?
+                };
+                if operator.is_numeric() && !operand_type.is_numeric() {
+                    Err(format!(
+                        "cannot use operator '{operator}' on {operand_type}"
+                    ))?;
                 }
 
                 if operator.is_comparison() {
                     Ok(ColumnType::Bool)
                 } else {
-                    Ok(left_type)
+                    Ok(operand_type)
                 }
             }
             Output::Function(function) => function.return_type(var_types),
@@ -371,6 +594,25 @@ impl Output {
             },
         }
     }
+
+    /// Checks whether this expression is a reference to a value that may be null.
+    const fn is_reference(&self) -> bool {
+        matches!(
+            self,
+            Output::Identifier(_) | Output::Function(Function::Next(_) | Function::Prev(_))
+        )
+    }
+
+    /// Checks whether this expression refers to `ident`.
+    fn references(&self, ident: &Ident) -> bool {
+        match self {
+            Output::Binary { left, right, .. } => left.references(ident) || right.references(ident),
+            Output::Function(function) => function.args().iter().any(|arg| arg.references(ident)),
+            Output::Identifier(name) => name == ident,
+            Output::Literal(_) => false,
+            Output::Unary { right, .. } => right.references(ident),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -393,6 +635,112 @@ impl TryFrom<&str> for Aggregate {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OnError {
+    Abort,
+    Invalid,
+}
+
+impl TryFrom<&str> for OnError {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "abort" => Ok(OnError::Abort),
+            "invalid" => Ok(OnError::Invalid),
+            _ => Err(format!("invalid value for 'on-error': '{value}'")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OnLongStreak {
+    Abort,
+    Delete,
+}
+
+impl TryFrom<&str> for OnLongStreak {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "abort" => Ok(OnLongStreak::Abort),
+            "delete" => Ok(OnLongStreak::Delete),
+            _ => Err(format!("invalid value for 'on-long-streak': '{value}'")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Statistic {
+    Mean,
+    Median,
+}
+
+impl TryFrom<&str> for Statistic {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "mean" => Ok(Statistic::Mean),
+            "median" => Ok(Statistic::Median),
+            _ => Err(format!("invalid value for 'statistic': '{value}'")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LengthUnit {
+    Bytes,
+    Chars,
+}
+
+impl TryFrom<&str> for LengthUnit {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "bytes" => Ok(LengthUnit::Bytes),
+            "chars" => Ok(LengthUnit::Chars),
+            _ => Err(format!("invalid value for 'length-unit': '{value}'")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Monotonic {
+    NonDecreasing,
+    StrictlyIncreasing,
+    NonIncreasing,
+    StrictlyDecreasing,
+}
+
+impl TryFrom<&str> for Monotonic {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "increasing" | "non-decreasing" => Ok(Monotonic::NonDecreasing),
+            "strictly-increasing" => Ok(Monotonic::StrictlyIncreasing),
+            "decreasing" | "non-increasing" => Ok(Monotonic::NonIncreasing),
+            "strictly-decreasing" => Ok(Monotonic::StrictlyDecreasing),
+            _ => Err(format!("invalid value for 'monotonic': '{value}'")),
+        }
+    }
+}
+
+/// A method of finding values that are far from the rest of the column.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Outliers {
+    /// Values more than this many standard deviations from the mean.
+    ZScore(f64),
+    /// Values more than this many interquartile ranges outside the quartiles.
+    Iqr(f64),
+    /// Values more than `threshold` scaled median absolute deviations from the median of the
+    /// `window` values on either side.
+    Hampel { window: usize, threshold: f64 },
+}
+
 #[derive(Debug, Clone)]
 struct Column {
     title: String,
@@ -402,26 +750,138 @@ struct Column {
     valid_values: Option<Vec<Value>>,
     on_invalid: OnInvalid,
     on_null: OnInvalid,
+    /// Whether values above `max` or below `min` are replaced with the bound, rather than being
+    /// treated as invalid.
+    clamp: bool,
+    /// The number of valid values used by `rolling`, and the statistic of them that is used.
+    rolling: Option<(usize, Statistic)>,
+    max_invalid_streak: Option<usize>,
+    max_null_streak: Option<usize>,
+    on_long_streak: OnLongStreak,
     max: Option<Value>,
     min: Option<Value>,
+    exclusive_max: Option<Value>,
+    exclusive_min: Option<Value>,
+    ranges: Option<Vec<(Option<Value>, Option<Value>)>>,
     invalid_values: Option<Vec<Value>>,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    length_unit: LengthUnit,
+    pattern: Option<Pattern>,
+    invalid_pattern: Option<Pattern>,
+    monotonic: Option<Monotonic>,
+    max_change: Option<Value>,
+    max_change_ratio: Option<f64>,
+    outliers: Option<Outliers>,
+    valid_if: Option<Output>,
     output: Output,
+    on_error: OnError,
     ignore: bool,
+    unique: Option<OnDuplicate>,
     aggregate: Aggregate,
     process_columns: Vec<(Ident, ColumnType)>,
+    variables: Vec<Ident>,
+    previous_outputs: Vec<(Ident, ColumnType)>,
+    provides_output: bool,
 }
 
 impl Column {
+    /// Checks whether rows can be deleted after they have been kept, because they are part of a
+    /// series of invalid values or null entries that is too long.
+    const fn deletes_long_streaks(&self) -> bool {
+        !self.ignore
+            && matches!(self.on_long_streak, OnLongStreak::Delete)
+            && (self.max_invalid_streak.is_some() || self.max_null_streak.is_some())
+    }
+
+    /// Gets the option that fills in missing values, if either `on-invalid` or `on-null` uses
+    /// one.
+    const fn fill(&self) -> Option<&OnInvalid> {
+        if self.on_invalid.fill_name().is_some() {
+            Some(&self.on_invalid)
+        } else if self.on_null.fill_name().is_some() {
+            Some(&self.on_null)
+        } else {
+            None
+        }
+    }
+
     const fn needs_state(&self) -> bool {
-        matches!(self.on_invalid, OnInvalid::Average(_))
-            || matches!(self.on_null, OnInvalid::Average(_))
+        self.fill().is_some()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Variable {
+    name: Ident,
+    output: Output,
+    output_type: ColumnType,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OnViolation {
+    Abort,
+    Delete,
+    Warn,
+}
+
+impl TryFrom<&str> for OnViolation {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "abort" => Ok(OnViolation::Abort),
+            "delete" => Ok(OnViolation::Delete),
+            "warn" => Ok(OnViolation::Warn),
+            _ => Err(format!("invalid value for 'on-violation': '{value}'")),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct RowConstraint {
+    /// The expression as written in the config, used in messages.
+    source: String,
+    constraint: Output,
+    on_violation: OnViolation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OnDuplicate {
+    Abort,
+    DeleteLater,
+    KeepLast,
+}
+
+impl TryFrom<&str> for OnDuplicate {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "abort" => Ok(OnDuplicate::Abort),
+            "delete-later" => Ok(OnDuplicate::DeleteLater),
+            "keep-last" => Ok(OnDuplicate::KeepLast),
+            _ => Err(format!("invalid value for 'on-duplicate': '{value}'")),
+        }
     }
 }
 
+/// A set of columns whose raw values must not be repeated across rows.
+#[derive(Debug, Clone)]
+struct DuplicateCheck {
+    columns: Vec<Ident>,
+    on_duplicate: OnDuplicate,
+    /// Whether rows with a null in any of the columns are skipped, rather than compared.
+    skip_nulls: bool,
+}
+
 #[derive(Debug, Clone)]
 struct Process {
     name: String,
     columns: Vec<Column>,
+    variables: Vec<Variable>,
+    row_constraints: Vec<RowConstraint>,
+    duplicate_checks: Vec<DuplicateCheck>,
     aggregate_column: Option<Ident>,
 }
 
@@ -500,6 +960,13 @@ impl TryFrom<Yaml> for OnTitle {
     }
 }
 
+/// Declarations that are shared by every process.
+#[derive(Debug, Clone, Default)]
+struct Globals {
+    functions: HashMap<Ident, UserFunction>,
+    constants: HashMap<Ident, Value>,
+}
+
 #[derive(Debug, Clone)]
 struct Program {
     processes: Vec<Process>,
@@ -518,7 +985,182 @@ fn ensure_empty(hash: &Hash, map_name: &str) {
     }
 }
 
-fn parse_column(input: Yaml) -> Column {
+/// Emits a compiler warning at the call site of the macro.
+fn warn(message: &str) {
+    proc_macro::Diagnostic::spanned(
+        proc_macro::Span::call_site(),
+        proc_macro::Level::Warning,
+        message,
+    )
+    .emit();
+}
+
+/// Folds a type checked expression, warning if it or any comparison in it has the same result
+/// for every row.
+fn fold_expression(output: &mut Output, var_types: &HashMap<Ident, ColumnType>, description: &str) {
+    let comparisons = output.constant_comparisons(var_types);
+    output.fold();
+    if output.is_constant(var_types) {
+        warn(&format!(
+            "{description}: the result is the same for every row"
+        ));
+    } else {
+        for message in comparisons {
+            warn(&format!("{description}: {message}"));
+        }
+    }
+}
+
+/// Gets the value of a scalar in the config, or `None` if it is null (`~`).
+///
+/// Strings are evaluated as constant expressions unless `column_type` is a string, in which case
+/// they are only treated as a reference if they are the name of a constant. Other scalars given
+/// for a string column are used as they are written.
+fn parse_constant(
+    yaml: &Yaml,
+    column_type: ColumnType,
+    key: &str,
+    globals: &Globals,
+) -> Option<Value> {
+    if let Some(value) = yaml
+        .as_str()
+        .and_then(parse_name)
+        .and_then(|ident| globals.constants.get(&ident))
+    {
+        return Some(value.clone());
+    }
+
+    let value = match (yaml, column_type) {
+        (Yaml::Null, _) => None?,
+        (Yaml::String(expression), column_type) if column_type != ColumnType::String => {
+            let mut output = parse_output(expression, globals);
+            if let Err(message) = output.return_type(&HashMap::new()) {
+                panic!("{key}: {message}")
+            }
+            output.fold();
+            let Output::Literal(value) = output else {
+                panic!("{key} must be a constant expression, found '{expression}'")
+            };
+            value
+        }
+        (Yaml::Boolean(b), ColumnType::String) => Value::String(b.to_string()),
+        (Yaml::Integer(i), ColumnType::String) => Value::String(i.to_string()),
+        (Yaml::Real(s), ColumnType::String) => Value::String(s.to_owned()),
+        (Yaml::Boolean(_) | Yaml::Integer(_) | Yaml::Real(_) | Yaml::String(_), _) => {
+            yaml.try_into().unwrap_or_else(|err| panic!("{key}: {err}"))
+        }
+        _ => panic!("{key} must be a single value"),
+    };
+
+    Some(value)
+}
+
+fn parse_value(
+    yaml: &Yaml,
+    column_type: ColumnType,
+    key: &str,
+    globals: &Globals,
+) -> Option<Value> {
+    let value = parse_constant(yaml, column_type, key, globals)?;
+    let value_type = ColumnType::from(&value);
+    let value = value
+        .promote(column_type)
+        .unwrap_or_else(|| panic!("the type of {key} must be {column_type}, found {value_type}"));
+    Some(value)
+}
+
+/// Parses a list of values, none of which can be null.
+fn parse_values(yaml: Yaml, column_type: ColumnType, key: &str, globals: &Globals) -> Vec<Value> {
+    yaml.into_vec()
+        .unwrap_or_else(|| panic!("{key} must be an array"))
+        .iter()
+        .map(|yaml| {
+            parse_value(yaml, column_type, key, globals)
+                .unwrap_or_else(|| panic!("{key} cannot contain null"))
+        })
+        .collect()
+}
+
+/// Parses the value of `max` or `min`.
+///
+/// A real bound on an integer column is converted to the equivalent integer bound using `round`.
+fn parse_bound(
+    yaml: &Yaml,
+    column_type: ColumnType,
+    key: &str,
+    round: fn(f64) -> f64,
+    globals: &Globals,
+) -> Option<Value> {
+    match (
+        parse_constant(yaml, column_type, key, globals)?,
+        column_type,
+    ) {
+        (Value::Real(bound), ColumnType::Integer) => {
+            let bound = round(bound);
+            assert!(
+                INTEGER_RANGE.contains(&bound),
+                "{key} is out of range for an integer column"
+            );
+            Some(Value::Integer(bound as i64))
+        }
+        _ => parse_value(yaml, column_type, key, globals),
+    }
+}
+
+fn parse_outliers(input: Yaml, key: &str, globals: &Globals) -> Outliers {
+    let mut input = input
+        .into_hash()
+        .unwrap_or_else(|| panic!("{key} must be a map"));
+
+    let method = input
+        .remove(&Yaml::from_str("method"))
+        .unwrap_or_else(|| panic!("{key} requires key 'method'"))
+        .into_string()
+        .unwrap_or_else(|| panic!("'method' of {key} must be a string"));
+
+    let threshold = input.remove(&Yaml::from_str("threshold")).and_then(|yaml| {
+        let Value::Real(threshold) = parse_value(
+            &yaml,
+            ColumnType::Float,
+            &format!("'threshold' of {key}"),
+            globals,
+        )?
+        else {
+            unreachable!("values are promoted to reals")
+        };
+        assert!(
+            threshold >= 0.0,
+            "'threshold' of {key} must not be negative"
+        );
+        Some(threshold)
+    });
+
+    let window = input.remove(&Yaml::from_str("window")).map(|yaml| {
+        assert_eq!(
+            method, "hampel",
+            "'window' of {key} requires method 'hampel'"
+        );
+        yaml.as_i64()
+            .and_then(|n| usize::try_from(n).ok())
+            .filter(|&n| n > 0)
+            .unwrap_or_else(|| panic!("'window' of {key} must be a positive integer"))
+    });
+
+    ensure_empty(&input, key);
+
+    match method.as_str() {
+        "zscore" => Outliers::ZScore(threshold.unwrap_or(3.0)),
+        "iqr" => Outliers::Iqr(threshold.unwrap_or(1.5)),
+        "hampel" => Outliers::Hampel {
+            window: window.unwrap_or(3),
+            threshold: threshold.unwrap_or(3.0),
+        },
+        _ => panic!("invalid value for 'method' of {key}: '{method}'"),
+    }
+}
+
+#[allow(clippy::too_many_lines)]
+fn parse_column(input: Yaml, globals: &Globals) -> Column {
     let mut input = input.into_hash().expect("'columns' entires must be maps");
 
     let title = input
@@ -539,6 +1181,16 @@ fn parse_column(input: Yaml) -> Column {
         .remove(&Yaml::from_str("ignore"))
         .is_some_and(|yaml| yaml.as_bool().expect("'ignore' must be a Boolean"));
 
+    let unique = input
+        .remove(&Yaml::from_str("unique"))
+        .is_some_and(|yaml| yaml.as_bool().expect("'unique' must be a Boolean"));
+    let on_duplicate = parse_on_duplicate(&mut input);
+    assert!(
+        unique || on_duplicate.is_none(),
+        "column '{title}': 'on-duplicate' requires 'unique'"
+    );
+    let unique = unique.then(|| on_duplicate.unwrap_or(OnDuplicate::Abort));
+
     if ignore {
         ensure_empty(&input, "column");
 
@@ -550,16 +1202,41 @@ fn parse_column(input: Yaml) -> Column {
             valid_values: None,
             on_invalid: OnInvalid::Abort,
             on_null: OnInvalid::Abort,
+            clamp: false,
+            rolling: None,
+            max_invalid_streak: None,
+            max_null_streak: None,
+            on_long_streak: OnLongStreak::Abort,
             max: None,
             min: None,
+            exclusive_max: None,
+            exclusive_min: None,
+            ranges: None,
             invalid_values: None,
+            min_length: None,
+            max_length: None,
+            length_unit: LengthUnit::Chars,
+            pattern: None,
+            invalid_pattern: None,
+            monotonic: None,
+            max_change: None,
+            max_change_ratio: None,
+            outliers: None,
+            valid_if: None,
             output: Output::Identifier(Ident::new("value", Span::call_site())),
+            on_error: OnError::Abort,
             ignore,
+            unique,
             aggregate: Aggregate::First,
             process_columns: vec![],
+            variables: vec![],
+            previous_outputs: vec![],
+            provides_output: false,
         };
     }
 
+    let key = |key: &str| format!("'{key}' in column '{title}'");
+
     let output_type = input
         .remove(&Yaml::from_str("output-type"))
         .map_or(column_type, |yaml| {
@@ -569,92 +1246,356 @@ fn parse_column(input: Yaml) -> Column {
                 .unwrap()
         });
 
-    let null_surrogates = input.remove(&Yaml::from_str("null-surrogates")).map(|yaml| {
-            yaml.into_vec()
-                .expect("'null-surrogate' must be an array")
+    let null_surrogates = input
+        .remove(&Yaml::from_str("null-surrogates"))
+        .map(|yaml| {
+            // Empty entries are always null, so a null surrogate has nothing left to match.
+            let surrogates = yaml
+                .into_vec()
+                .unwrap_or_else(|| panic!("{} must be an array", key("null-surrogates")))
                 .into_iter()
-                .map(|yaml| {
-                    let value: Value = yaml.try_into().unwrap();
-                    assert_eq!(ColumnType::from(&value), column_type, "the type of the values in 'null-surrogate' must be the same as 'columm-type'");
-                    value
-                })
-                .collect()
+                .filter(|yaml| !yaml.is_null())
+                .collect();
+            parse_values(
+                Yaml::Array(surrogates),
+                column_type,
+                &key("null-surrogates"),
+                globals,
+            )
         });
 
-    let valid_values = input.remove(&Yaml::from_str("valid-values")).map(|yaml| {
-        yaml.into_vec()
-            .expect("'valid-values' must be an array")
-            .into_iter()
-            .map(|yaml| {
-                let value: Value = yaml.try_into().unwrap();
-                assert_eq!(
-                    ColumnType::from(&value),
-                    column_type,
-                    "the type of the values in 'valid-values' must be the same as 'columm-type'"
-                );
-                value
-            })
-            .collect()
-    });
-
-    let on_invalid = input
-        .remove(&Yaml::from_str("on-invalid"))
-        .map_or(OnInvalid::Abort, |yaml| {
-            get_on_invalid(&yaml, &mut input, "invalid")
-        });
+    let valid_values = input
+        .remove(&Yaml::from_str("valid-values"))
+        .map(|yaml| parse_values(yaml, column_type, &key("valid-values"), globals));
+
+    // Clamping only applies to 'max' and 'min', so any other invalid values use the fallback.
+    let (on_invalid, clamp) =
+        input
+            .remove(&Yaml::from_str("on-invalid"))
+            .map_or((OnInvalid::Abort, false), |yaml| {
+                if yaml.as_str() == Some("clamp") {
+                    let fallback = input
+                        .remove(&Yaml::from_str("clamp-fallback"))
+                        .unwrap_or_else(|| Yaml::from_str("abort"));
+                    assert_ne!(
+                        fallback.as_str(),
+                        Some("clamp"),
+                        "value of 'clamp-fallback' cannot be 'clamp'"
+                    );
+                    let fallback = get_on_invalid(
+                        &fallback,
+                        &mut input,
+                        "invalid",
+                        output_type,
+                        &title,
+                        globals,
+                    );
+                    (fallback, true)
+                } else {
+                    let on_invalid =
+                        get_on_invalid(&yaml, &mut input, "invalid", output_type, &title, globals);
+                    (on_invalid, false)
+                }
+            });
 
+    // Null entries and invalid values share the settings of an option that fills in missing
+    // values, so they are only read once.
     let on_null = input
         .remove(&Yaml::from_str("on-null"))
         .map_or(OnInvalid::Abort, |yaml| {
-            get_on_invalid(&yaml, &mut input, "null")
+            if on_invalid.fill_name().is_some() && yaml.as_str() == on_invalid.fill_name() {
+                on_invalid.clone()
+            } else {
+                get_on_invalid(&yaml, &mut input, "null", output_type, &title, globals)
+            }
+        });
+
+    if let Some(name) = on_null.fill_name() {
+        assert!(
+            on_null == on_invalid || matches!(on_invalid, OnInvalid::Abort | OnInvalid::Delete),
+            "'on-null' can only be '{name}' if 'on-invalid' is also '{name}', 'abort' or 'delete'"
+        );
+    }
+    for on_missing in [&on_invalid, &on_null] {
+        assert!(
+            !matches!(on_missing, OnInvalid::Average(_)) || output_type.is_numeric(),
+            "'{output_type}' cannot be averaged"
+        );
+        assert!(
+            !matches!(on_missing, OnInvalid::Interpolate(_, _)) || output_type.is_numeric(),
+            "'{output_type}' cannot be interpolated"
+        );
+    }
+
+    let rolling = (matches!(on_invalid, OnInvalid::Rolling(_))
+        || matches!(on_null, OnInvalid::Rolling(_)))
+    .then(|| {
+        assert!(
+            output_type.is_numeric(),
+            "'rolling' option requires a numeric output, found '{output_type}'"
+        );
+        let window = input
+            .remove(&Yaml::from_str("window"))
+            .expect("'rolling' option requires key 'window'")
+            .as_i64()
+            .and_then(|n| usize::try_from(n).ok())
+            .filter(|&n| n > 0)
+            .expect("'window' must be a positive integer");
+        let statistic =
+            input
+                .remove(&Yaml::from_str("statistic"))
+                .map_or(Statistic::Mean, |yaml| {
+                    yaml.as_str()
+                        .expect("value of 'statistic' must be a string")
+                        .try_into()
+                        .unwrap()
+                });
+        (window, statistic)
+    });
+
+    let [max_invalid_streak, max_null_streak] =
+        ["max-invalid-streak", "max-null-streak"].map(|name| {
+            input.remove(&Yaml::from_str(name)).map(|yaml| {
+                yaml.as_i64()
+                    .and_then(|n| usize::try_from(n).ok())
+                    .unwrap_or_else(|| panic!("{} must be a non-negative integer", key(name)))
+            })
         });
 
+    let on_long_streak =
+        input
+            .remove(&Yaml::from_str("on-long-streak"))
+            .map_or(OnLongStreak::Abort, |yaml| {
+                assert!(
+                    max_invalid_streak.is_some() || max_null_streak.is_some(),
+                    "{} requires 'max-invalid-streak' or 'max-null-streak'",
+                    key("on-long-streak")
+                );
+                yaml.as_str()
+                    .expect("value of 'on-long-streak' must be a string")
+                    .try_into()
+                    .unwrap()
+            });
+
+    let max = input
+        .remove(&Yaml::from_str("max"))
+        .and_then(|yaml| parse_bound(&yaml, column_type, &key("max"), f64::floor, globals));
+
+    let min = input
+        .remove(&Yaml::from_str("min"))
+        .and_then(|yaml| parse_bound(&yaml, column_type, &key("min"), f64::ceil, globals));
+
     assert!(
-        !matches!(on_null, OnInvalid::Average(_)) || matches!(on_invalid, OnInvalid::Average(_)),
-        "'on-null' can only be 'average' if 'on-invalid' is also 'average'"
+        !clamp || max.is_some() || min.is_some(),
+        "'clamp' option for on-invalid requires 'max' or 'min', as 'exclusive-max', \
+         'exclusive-min' and 'ranges' are not clamped"
     );
 
-    let max = input.remove(&Yaml::from_str("max")).map(|yaml| {
-        let value: Value = yaml.try_into().unwrap();
-        assert_eq!(
-            ColumnType::from(&value),
-            column_type,
-            "the type of 'max' must be the same as 'columm-type'"
-        );
-        value
+    if let (Some(min), Some(max)) = (&min, &max)
+        && min > max
+    {
+        warn(&format!(
+            "column '{title}': 'min' is greater than 'max', so every value is invalid"
+        ));
+    }
+
+    let exclusive_max = input
+        .remove(&Yaml::from_str("exclusive-max"))
+        .and_then(|yaml| {
+            parse_bound(
+                &yaml,
+                column_type,
+                &key("exclusive-max"),
+                f64::ceil,
+                globals,
+            )
+        });
+
+    let exclusive_min = input
+        .remove(&Yaml::from_str("exclusive-min"))
+        .and_then(|yaml| {
+            parse_bound(
+                &yaml,
+                column_type,
+                &key("exclusive-min"),
+                f64::floor,
+                globals,
+            )
+        });
+
+    let ranges = input.remove(&Yaml::from_str("ranges")).map(|yaml| {
+        let ranges: Vec<_> = yaml
+            .into_vec()
+            .unwrap_or_else(|| panic!("{} must be an array", key("ranges")))
+            .into_iter()
+            .map(|range| {
+                let Some([low, high]) = range
+                    .into_vec()
+                    .and_then(|range| <[Yaml; 2]>::try_from(range).ok())
+                else {
+                    panic!("{} must contain pairs of bounds", key("ranges"))
+                };
+                let low = parse_bound(&low, column_type, &key("ranges"), f64::ceil, globals);
+                let high = parse_bound(&high, column_type, &key("ranges"), f64::floor, globals);
+                if let (Some(low), Some(high)) = (&low, &high)
+                    && low > high
+                {
+                    warn(&format!(
+                        "column '{title}': the lower bound of a range is greater than its upper \
+                         bound, so no values are in it"
+                    ));
+                }
+                (low, high)
+            })
+            .collect();
+        if ranges.is_empty() {
+            warn(&format!(
+                "column '{title}': 'ranges' is empty, so every value is invalid"
+            ));
+        }
+        ranges
     });
 
-    let min = input.remove(&Yaml::from_str("min")).map(|yaml| {
-        let value: Value = yaml.try_into().unwrap();
-        assert_eq!(
-            ColumnType::from(&value),
-            column_type,
-            "the type of 'min' must be the same as 'columm-type'"
-        );
-        value
+    let invalid_values = input
+        .remove(&Yaml::from_str("invalid-values"))
+        .map(|yaml| parse_values(yaml, column_type, &key("invalid-values"), globals));
+
+    let [min_length, max_length] = ["min-length", "max-length"].map(|name| {
+        input.remove(&Yaml::from_str(name)).and_then(|yaml| {
+            assert_eq!(
+                column_type,
+                ColumnType::String,
+                "{} can only be used with string columns",
+                key(name)
+            );
+            let Value::Integer(length) =
+                parse_value(&yaml, ColumnType::Integer, &key(name), globals)?
+            else {
+                unreachable!("integer values are always parsed as integers")
+            };
+            let length = usize::try_from(length)
+                .unwrap_or_else(|_| panic!("{} must not be negative", key(name)));
+            Some(length)
+        })
     });
 
-    let invalid_values = input.remove(&Yaml::from_str("invalid-values")).map(|yaml| {
-        yaml.into_vec()
-            .expect("'invalid-values' must be an array")
-            .into_iter()
-            .map(|yaml| {
-                let value: Value = yaml.try_into().unwrap();
+    let length_unit =
+        input
+            .remove(&Yaml::from_str("length-unit"))
+            .map_or(LengthUnit::Chars, |yaml| {
                 assert_eq!(
-                    ColumnType::from(&value),
                     column_type,
-                    "the type of the values in 'invalid-values' must be the same as 'columm-type'"
+                    ColumnType::String,
+                    "{} can only be used with string columns",
+                    key("length-unit")
                 );
-                value
-            })
-            .collect()
+                yaml.as_str()
+                    .expect("value of 'length-unit' must be a string")
+                    .try_into()
+                    .unwrap()
+            });
+
+    let [pattern, invalid_pattern] = ["pattern", "invalid-pattern"].map(|name| {
+        input.remove(&Yaml::from_str(name)).map(|yaml| {
+            assert_eq!(
+                column_type,
+                ColumnType::String,
+                "{} can only be used with string columns",
+                key(name)
+            );
+            let pattern = yaml
+                .into_string()
+                .unwrap_or_else(|| panic!("{} must be a string", key(name)));
+            Pattern::new(&pattern).unwrap_or_else(|err| panic!("{}: {err}", key(name)))
+        })
+    });
+
+    let monotonic = input.remove(&Yaml::from_str("monotonic")).map(|yaml| {
+        assert_ne!(
+            column_type,
+            ColumnType::Bool,
+            "{} cannot be used with boolean columns",
+            key("monotonic")
+        );
+        yaml.as_str()
+            .expect("value of 'monotonic' must be a string")
+            .try_into()
+            .unwrap()
+    });
+
+    let max_change = input
+        .remove(&Yaml::from_str("max-change"))
+        .and_then(|yaml| {
+            assert!(
+                column_type.is_numeric(),
+                "{} can only be used with numeric columns",
+                key("max-change")
+            );
+            parse_bound(&yaml, column_type, &key("max-change"), f64::floor, globals)
+        });
+    assert!(
+        !matches!(max_change, Some(Value::Integer(i)) if i < 0)
+            && !matches!(max_change, Some(Value::Real(r)) if r < 0.0),
+        "{} must not be negative",
+        key("max-change")
+    );
+
+    let max_change_ratio = input
+        .remove(&Yaml::from_str("max-change-ratio"))
+        .and_then(|yaml| {
+            assert!(
+                column_type.is_numeric(),
+                "{} can only be used with numeric columns",
+                key("max-change-ratio")
+            );
+            let Value::Real(ratio) =
+                parse_value(&yaml, ColumnType::Float, &key("max-change-ratio"), globals)?
+            else {
+                unreachable!("values are promoted to reals")
+            };
+            Some(ratio)
+        });
+    assert!(
+        max_change_ratio.is_none_or(|ratio| ratio >= 0.0),
+        "{} must not be negative",
+        key("max-change-ratio")
+    );
+
+    let outliers = input.remove(&Yaml::from_str("outliers")).map(|yaml| {
+        assert!(
+            column_type.is_numeric(),
+            "{} can only be used with numeric columns",
+            key("outliers")
+        );
+        parse_outliers(yaml, &key("outliers"), globals)
+    });
+
+    let valid_if = input.remove(&Yaml::from_str("valid-if")).map(|yaml| {
+        parse_output(
+            &yaml.into_string().expect("'valid-if' must be a string"),
+            globals,
+        )
     });
 
     let output = input.remove(&Yaml::from_str("output")).map_or_else(
         || Output::Identifier(Ident::new("value", Span::call_site())),
-        |yaml| parse_output(&yaml.into_string().expect("'output' must be a string")),
+        |yaml| {
+            parse_output(
+                &yaml.into_string().expect("'output' must be a string"),
+                globals,
+            )
+        },
     );
 
+    let on_error = input
+        .remove(&Yaml::from_str("on-error"))
+        .map_or(OnError::Abort, |yaml| {
+            yaml.as_str()
+                .expect("value of 'on-error' must be a string")
+                .try_into()
+                .unwrap()
+        });
+
     let aggregate = input
         .remove(&Yaml::from_str("aggregate"))
         .map_or(Aggregate::First, |yaml| {
@@ -678,17 +1619,84 @@ fn parse_column(input: Yaml) -> Column {
         valid_values,
         on_invalid,
         on_null,
+        clamp,
+        rolling,
+        max_invalid_streak,
+        max_null_streak,
+        on_long_streak,
         max,
         min,
+        exclusive_max,
+        exclusive_min,
+        ranges,
         invalid_values,
+        min_length,
+        max_length,
+        length_unit,
+        pattern,
+        invalid_pattern,
+        monotonic,
+        max_change,
+        max_change_ratio,
+        outliers,
+        valid_if,
         output,
+        on_error,
         ignore,
+        unique,
         aggregate,
         process_columns: vec![],
+        variables: vec![],
+        previous_outputs: vec![],
+        provides_output: false,
+    }
+}
+
+fn parse_on_duplicate(input: &mut Hash) -> Option<OnDuplicate> {
+    input.remove(&Yaml::from_str("on-duplicate")).map(|yaml| {
+        yaml.as_str()
+            .expect("value of 'on-duplicate' must be a string")
+            .try_into()
+            .unwrap()
+    })
+}
+
+fn parse_row_constraint(input: Yaml, globals: &Globals) -> RowConstraint {
+    let mut input = input
+        .into_hash()
+        .expect("'row-constraints' entries must be maps");
+
+    let source = input
+        .remove(&Yaml::from_str("constraint"))
+        .expect("row constraint requires key 'constraint'")
+        .into_string()
+        .expect("'constraint' must be a string");
+
+    let on_violation =
+        input
+            .remove(&Yaml::from_str("on-violation"))
+            .map_or(OnViolation::Abort, |yaml| {
+                yaml.as_str()
+                    .expect("value of 'on-violation' must be a string")
+                    .try_into()
+                    .unwrap()
+            });
+    assert!(
+        on_violation != OnViolation::Warn || cfg!(feature = "std"),
+        "'on-violation: warn' requires the 'std' feature"
+    );
+
+    ensure_empty(&input, "row constraint");
+
+    RowConstraint {
+        constraint: parse_output(&source, globals),
+        source,
+        on_violation,
     }
 }
 
-fn parse_process(input: Yaml) -> Process {
+#[allow(clippy::too_many_lines)]
+fn parse_process(input: Yaml, globals: &Globals) -> Process {
     let mut input = input.into_hash().expect("'processes' entires must be maps");
 
     let name = input
@@ -703,9 +1711,57 @@ fn parse_process(input: Yaml) -> Process {
         .into_vec()
         .expect("'columns' must be an array")
         .into_iter()
-        .map(parse_column)
+        .map(|yaml| parse_column(yaml, globals))
         .collect();
 
+    let variables: Vec<(Ident, Output)> =
+        input
+            .remove(&Yaml::from_str("variables"))
+            .map_or_else(Vec::new, |yaml| {
+                yaml.into_hash()
+                    .expect("'variables' must be a map")
+                    .into_iter()
+                    .map(|(name, value)| {
+                        let name = name.into_string().expect("variable names must be strings");
+                        let Some(ident) = parse_name(&name) else {
+                            panic!("invalid variable name '{name}'")
+                        };
+                        let value = value.into_string().unwrap_or_else(|| {
+                            panic!("value of variable '{name}' must be a string")
+                        });
+                        (ident, parse_output(&value, globals))
+                    })
+                    .collect()
+            });
+
+    let row_constraints = input
+        .remove(&Yaml::from_str("row-constraints"))
+        .map_or_else(Vec::new, |yaml| {
+            yaml.into_vec()
+                .expect("'row-constraints' must be an array")
+                .into_iter()
+                .map(|yaml| parse_row_constraint(yaml, globals))
+                .collect()
+        });
+
+    let dedupe: Option<Vec<Ident>> = input.remove(&Yaml::from_str("dedupe")).map(|yaml| {
+        yaml.into_vec()
+            .expect("'dedupe' must be an array")
+            .into_iter()
+            .map(|yaml| {
+                Ident::new(
+                    yaml.as_str().expect("values of 'dedupe' must be strings"),
+                    Span::call_site(),
+                )
+            })
+            .collect()
+    });
+    let on_duplicate = parse_on_duplicate(&mut input);
+    assert!(
+        dedupe.is_some() || on_duplicate.is_none(),
+        "process '{name}': 'on-duplicate' requires 'dedupe'"
+    );
+
     let aggregate_column = input
         .remove(&Yaml::from_str("aggregate-column"))
         .map(|yaml| {
@@ -721,18 +1777,62 @@ fn parse_process(input: Yaml) -> Process {
     let mut process = Process {
         name,
         columns,
+        variables: vec![],
+        row_constraints,
+        duplicate_checks: vec![],
         aggregate_column,
     };
 
     let names = process.column_names();
     let column_types = process.column_types();
-    for (i, (name, column_type)) in zip(&names, column_types).enumerate() {
-        for (j, column) in process.columns.iter_mut().enumerate() {
-            if i == j {
-                continue;
-            }
+    for column in &mut process.columns {
+        column.process_columns = zip(&names, &column_types)
+            .map(|(name, column_type)| (name.clone(), *column_type))
+            .collect();
+    }
+
+    for column in &process.columns {
+        if let Some(on_duplicate) = column.unique {
+            process.duplicate_checks.push(DuplicateCheck {
+                columns: vec![Ident::new(&column.title, Span::call_site())],
+                on_duplicate,
+                skip_nulls: true,
+            });
+        }
+    }
+    if let Some(columns) = dedupe {
+        assert!(
+            !columns.is_empty(),
+            "process '{}': 'dedupe' must not be empty",
+            process.name
+        );
+        for column in &columns {
+            assert!(
+                names.contains(column),
+                "process '{}': dedupe value '{column}' is not a column",
+                process.name
+            );
+        }
+        process.duplicate_checks.push(DuplicateCheck {
+            columns,
+            on_duplicate: on_duplicate.unwrap_or(OnDuplicate::Abort),
+            skip_nulls: false,
+        });
+    }
 
-            column.process_columns.push((name.clone(), column_type));
+    for column in &process.columns {
+        if let Some((by, key)) = column.fill().and_then(OnInvalid::position_column) {
+            let position = names.iter().position(|name| name == by).unwrap_or_else(|| {
+                panic!(
+                    "column '{}': {key} value '{by}' is not a column",
+                    column.title
+                )
+            });
+            assert!(
+                column_types[position].is_numeric(),
+                "column '{}': {key} column '{by}' must be numeric",
+                column.title
+            );
         }
     }
 
@@ -743,23 +1843,99 @@ fn parse_process(input: Yaml) -> Process {
         );
     }
 
-    let mut var_types: HashMap<Ident, ColumnType> = process
-        .columns
+    let mut var_types = HashMap::new();
+    for (name, column_type) in zip(&names, column_types) {
+        for prefix in ["value", "raw"] {
+            for offset in ["", "prev_", "next_"] {
+                var_types.insert(
+                    Ident::new(&format!("{offset}{prefix}_{name}"), Span::call_site()),
+                    column_type,
+                );
+            }
+        }
+    }
+    for context_ident in ["row", "line", "section"] {
+        var_types.insert(
+            Ident::new(context_ident, Span::call_site()),
+            ColumnType::Integer,
+        );
+    }
+    let value_ident = Ident::new("value", Span::call_site());
+    let prev_value_ident = Ident::new("prev_value", Span::call_site());
+    let next_value_ident = Ident::new("next_value", Span::call_site());
+
+    for name in globals.constants.keys() {
+        assert!(
+            !var_types.contains_key(name)
+                && ![&value_ident, &prev_value_ident, &next_value_ident].contains(&name)
+                && !names.iter().any(|title| *name == format!("out_{title}")),
+            "process '{}': constant name '{name}' is already in use",
+            process.name
+        );
+    }
+
+    for (name, mut output) in variables {
+        assert!(
+            !var_types.contains_key(&name)
+                && !globals.constants.contains_key(&name)
+                && ![&value_ident, &prev_value_ident, &next_value_ident].contains(&&name)
+                && !names.iter().any(|title| name == format!("out_{title}")),
+            "process '{}': variable name '{name}' is already in use",
+            process.name
+        );
+        let output_type = match output.return_type(&var_types) {
+            Ok(output_type) => output_type,
+            Err(message) => panic!("process '{}', variable '{name}': {message}", process.name),
+        };
+        fold_expression(
+            &mut output,
+            &var_types,
+            &format!("process '{}', variable '{name}'", process.name),
+        );
+        var_types.insert(name.clone(), output_type);
+        process.variables.push(Variable {
+            name,
+            output,
+            output_type,
+        });
+    }
+    for row_constraint in &mut process.row_constraints {
+        let source = &row_constraint.source;
+        match row_constraint.constraint.return_type(&var_types) {
+            Ok(ColumnType::Bool) => {}
+            Ok(found) => panic!(
+                "process '{}', row constraint '{source}': expected boolean, found {found}",
+                process.name
+            ),
+            Err(message) => panic!(
+                "process '{}', row constraint '{source}': {message}",
+                process.name
+            ),
+        }
+        fold_expression(
+            &mut row_constraint.constraint,
+            &var_types,
+            &format!("process '{}', row constraint '{source}'", process.name),
+        );
+    }
+
+    let variable_names: Vec<Ident> = process
+        .variables
         .iter()
-        .map(|column| {
-            (
-                Ident::new(
-                    &format!("value_{}", column.title.clone()),
-                    Span::call_site(),
-                ),
-                column.output_type,
-            )
-        })
+        .map(|variable| variable.name.clone())
         .collect();
-    let value_ident = Ident::new("value", Span::call_site());
 
-    for column in &process.columns {
+    let mut previous_outputs = vec![];
+    for column in &mut process.columns {
+        column.variables.clone_from(&variable_names);
+        column.previous_outputs.clone_from(&previous_outputs);
+        if column.ignore {
+            continue;
+        }
+
         var_types.insert(value_ident.clone(), column.input_type);
+        var_types.insert(prev_value_ident.clone(), column.input_type);
+        var_types.insert(next_value_ident.clone(), column.input_type);
         let return_type = match column.output.return_type(&var_types) {
             Ok(return_type) => return_type,
             Err(message) => panic!(
@@ -772,22 +1948,191 @@ fn parse_process(input: Yaml) -> Process {
             "process '{}', column '{}': expected {}, found {return_type}",
             process.name, column.title, column.output_type
         );
+        fold_expression(
+            &mut column.output,
+            &var_types,
+            &format!(
+                "process '{}', column '{}', 'output'",
+                process.name, column.title
+            ),
+        );
+        if let Some(valid_if) = &mut column.valid_if {
+            match valid_if.return_type(&var_types) {
+                Ok(ColumnType::Bool) => {}
+                Ok(found) => panic!(
+                    "process '{}', column '{}': 'valid-if' must be boolean, found {found}",
+                    process.name, column.title
+                ),
+                Err(message) => panic!(
+                    "process '{}', column '{}': {message}",
+                    process.name, column.title
+                ),
+            }
+            fold_expression(
+                valid_if,
+                &var_types,
+                &format!(
+                    "process '{}', column '{}', 'valid-if'",
+                    process.name, column.title
+                ),
+            );
+        }
         var_types.remove(&value_ident);
+        var_types.remove(&prev_value_ident);
+        var_types.remove(&next_value_ident);
+
+        let out_ident = Ident::new(&format!("out_{}", column.title), Span::call_site());
+        var_types.insert(out_ident.clone(), column.output_type);
+        previous_outputs.push((out_ident, column.output_type));
+    }
+
+    for column in &mut process.columns {
+        let output = &column.output;
+        let valid_if = &column.valid_if;
+        column.previous_outputs.retain(|(out_ident, _)| {
+            output.references(out_ident)
+                || valid_if
+                    .as_ref()
+                    .is_some_and(|valid_if| valid_if.references(out_ident))
+        });
+    }
+    for i in 0..process.columns.len() {
+        let out_ident = Ident::new(
+            &format!("out_{}", process.columns[i].title),
+            Span::call_site(),
+        );
+        process.columns[i].provides_output = process.columns.iter().any(|column| {
+            column
+                .previous_outputs
+                .iter()
+                .any(|(ident, _)| *ident == out_ident)
+        });
     }
 
     process
 }
 
+fn parse_function(name: Yaml, input: Yaml) -> UserFunction {
+    let name = name.into_string().expect("function names must be strings");
+    assert!(
+        !is_function_name(&name),
+        "function name '{name}' is reserved for a built-in function"
+    );
+    let Some(ident) = parse_name(&name) else {
+        panic!("invalid function name '{name}'")
+    };
+    let mut input = input
+        .into_hash()
+        .unwrap_or_else(|| panic!("function '{name}' must be a map"));
+
+    let path = input
+        .remove(&Yaml::from_str("path"))
+        .unwrap_or_else(|| panic!("function '{name}': 'path' key required"))
+        .into_string()
+        .unwrap_or_else(|| panic!("function '{name}': 'path' must be a string"));
+    let path = syn::parse_str(&path)
+        .unwrap_or_else(|err| panic!("function '{name}': invalid path '{path}': {err}"));
+
+    let args = input
+        .remove(&Yaml::from_str("args"))
+        .map_or_else(Vec::new, |yaml| {
+            yaml.into_vec()
+                .unwrap_or_else(|| panic!("function '{name}': 'args' must be an array"))
+                .into_iter()
+                .map(|yaml| {
+                    yaml.into_string()
+                        .unwrap_or_else(|| panic!("function '{name}': types must be strings"))
+                        .try_into()
+                        .unwrap()
+                })
+                .collect()
+        });
+
+    let returns = input
+        .remove(&Yaml::from_str("returns"))
+        .unwrap_or_else(|| panic!("function '{name}': 'returns' key required"))
+        .into_string()
+        .unwrap_or_else(|| panic!("function '{name}': types must be strings"))
+        .try_into()
+        .unwrap();
+
+    ensure_empty(&input, "function");
+
+    UserFunction {
+        name: ident,
+        path,
+        args,
+        returns,
+    }
+}
+
+/// Parses an entry in the root `constants` section, which may refer to constants before it.
+fn parse_global_constant(name: Yaml, input: Yaml, globals: &Globals) -> (Ident, Value) {
+    let name = name.into_string().expect("constant names must be strings");
+    let Some(ident) = parse_name(&name) else {
+        panic!("invalid constant name '{name}'")
+    };
+    assert!(
+        !globals.constants.contains_key(&ident),
+        "constant '{name}' is defined more than once"
+    );
+    let mut input = input
+        .into_hash()
+        .unwrap_or_else(|| panic!("constant '{name}' must be a map"));
+
+    let constant_type = input
+        .remove(&Yaml::from_str("type"))
+        .unwrap_or_else(|| panic!("constant '{name}': 'type' key required"))
+        .into_string()
+        .unwrap_or_else(|| panic!("constant '{name}': 'type' must be a string"))
+        .try_into()
+        .unwrap();
+
+    let value = input
+        .remove(&Yaml::from_str("value"))
+        .and_then(|yaml| parse_value(&yaml, constant_type, &format!("constant '{name}'"), globals))
+        .unwrap_or_else(|| panic!("constant '{name}': 'value' key required"));
+
+    ensure_empty(&input, "constant");
+
+    (ident, value)
+}
+
 fn parse_program(input: Yaml, csv: Expr, string_input: bool) -> Program {
     let mut program = input.into_hash().expect("config must be a map");
 
+    let functions =
+        program
+            .remove(&Yaml::from_str("functions"))
+            .map_or_else(HashMap::new, |yaml| {
+                yaml.into_hash()
+                    .expect("'functions' must be a map")
+                    .into_iter()
+                    .map(|(name, yaml)| {
+                        let function = parse_function(name, yaml);
+                        (function.name.clone(), function)
+                    })
+                    .collect()
+            });
+    let mut globals = Globals {
+        functions,
+        constants: HashMap::new(),
+    };
+
+    if let Some(constants) = program.remove(&Yaml::from_str("constants")) {
+        for (name, yaml) in constants.into_hash().expect("'constants' must be a map") {
+            let (ident, value) = parse_global_constant(name, yaml, &globals);
+            globals.constants.insert(ident, value);
+        }
+    }
+
     let processes = program
         .remove(&Yaml::from_str("processes"))
         .expect("'processes' key is required")
         .into_vec()
         .expect("'processes' must be an array")
         .into_iter()
-        .map(parse_process)
+        .map(|yaml| parse_process(yaml, &globals))
         .collect();
 
     let on_title = program
@@ -1,12 +1,13 @@
-use crate::{BinOp, Function, Output, UnOp, Value};
+use crate::{BinOp, Function, Globals, Output, UnOp, Value};
 
-use std::str::FromStr;
+use std::{mem, str::FromStr};
 
 use proc_macro::TokenStream;
 use syn::{
-    parenthesized, parse,
+    Ident, LitBool, LitFloat, LitInt, LitStr, Result, Token, parenthesized, parse,
     parse::{Parse, ParseStream},
-    Ident, LitBool, LitFloat, LitInt, LitStr, Result, Token,
+    punctuated::Punctuated,
+    token,
 };
 
 mod kw {
@@ -14,14 +15,26 @@ mod kw {
 
     custom_keyword!(boolean);
     custom_keyword!(ceiling);
+    custom_keyword!(coalesce);
     custom_keyword!(concat);
+    custom_keyword!(default);
     custom_keyword!(floor);
+    custom_keyword!(format);
     custom_keyword!(integer);
+    custom_keyword!(is_null);
+    custom_keyword!(next);
+    custom_keyword!(prev);
     custom_keyword!(real);
     custom_keyword!(round);
     custom_keyword!(string);
 }
 
+/// The names of the built-in functions, which are keywords in expressions.
+const FUNCTION_NAMES: [&str; 14] = [
+    "boolean", "ceiling", "coalesce", "concat", "default", "floor", "format", "integer", "is_null",
+    "next", "prev", "real", "round", "string",
+];
+
 impl Parse for BinOp {
     fn parse(input: ParseStream) -> Result<Self> {
         let operator = if input.peek(Token![+]) {
@@ -38,14 +51,14 @@ impl Parse for BinOp {
             BinOp::Eq
         } else if input.peek(Token![!=]) {
             BinOp::Ne
-        } else if input.peek(Token![>]) {
-            BinOp::Gt
         } else if input.peek(Token![>=]) {
             BinOp::Ge
-        } else if input.peek(Token![<]) {
-            BinOp::Lt
+        } else if input.peek(Token![>]) {
+            BinOp::Gt
         } else if input.peek(Token![<=]) {
             BinOp::Le
+        } else if input.peek(Token![<]) {
+            BinOp::Lt
         } else {
             let message = format!("expected a binary operator, found '{input}'");
             Err(input.error(message))?
@@ -58,7 +71,10 @@ impl Parse for BinOp {
 }
 
 fn primary(input: ParseStream) -> Result<Output> {
-    if input.peek(Ident) {
+    if input.peek(Ident) && input.peek2(token::Paren) {
+        let name: Ident = input.parse()?;
+        Ok(Output::Function(Function::Call(name, arg_list(input)?)))
+    } else if input.peek(Ident) {
         let ident: Ident = input.parse()?;
         Ok(Output::Identifier(ident))
     } else if input.peek(LitStr) {
@@ -98,6 +114,90 @@ fn args(input: ParseStream) -> Result<(Output, Output)> {
     Ok((arg1, arg2))
 }
 
+/// Gets the value of `output` if it is a literal, including negative numbers.
+fn literal(output: Output) -> Option<Value> {
+    match output {
+        Output::Literal(value) => Some(value),
+        Output::Unary {
+            operator: UnOp::Negate,
+            right,
+        } => match *right {
+            Output::Literal(Value::Integer(i)) => Some(Value::Integer(-i)),
+            Output::Literal(Value::Real(r)) => Some(Value::Real(-r)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn arg_list(input: ParseStream) -> Result<Vec<Output>> {
+    let content;
+    parenthesized!(content in input);
+    let args = Punctuated::<Output, Token![,]>::parse_terminated(&content)?;
+    Ok(args.into_iter().collect())
+}
+
+/// Checks a format specification, such as `>8.2` or `08x`, and returns its conversion.
+fn format_spec(spec: &str) -> std::result::Result<&str, String> {
+    let mut rest = spec;
+    let mut chars = rest.chars();
+    match (chars.next(), chars.next()) {
+        (Some(fill), Some('<' | '^' | '>')) => rest = &rest[fill.len_utf8() + 1..],
+        (Some('<' | '^' | '>'), _) => rest = &rest[1..],
+        _ => {}
+    }
+    for flag in ['+', '-', '#', '0'] {
+        rest = rest.strip_prefix(flag).unwrap_or(rest);
+    }
+    rest = rest.trim_start_matches(|c: char| c.is_ascii_digit());
+    if let Some(precision) = rest.strip_prefix('.') {
+        rest = precision.trim_start_matches(|c: char| c.is_ascii_digit());
+    }
+
+    match rest {
+        "" | "?" | "x?" | "X?" | "x" | "X" | "o" | "b" | "e" | "E" => Ok(rest),
+        _ => Err(format!("invalid format specification '{spec}'")),
+    }
+}
+
+/// Gets the conversion used by each placeholder in `format`, such as `x` for `{:x}`.
+///
+/// # Errors
+/// Returns an error if `format` is not a valid format string, or uses positional or named
+/// arguments.
+pub(crate) fn format_placeholders(format: &str) -> std::result::Result<Vec<&str>, String> {
+    let mut placeholders = vec![];
+    let mut rest = format;
+    while let Some(i) = rest.find(['{', '}']) {
+        if rest[i..].starts_with("{{") || rest[i..].starts_with("}}") {
+            rest = &rest[i + 2..];
+        } else if rest[i..].starts_with('}') {
+            Err(format!("unmatched '}}' in format string '{format}'"))?;
+        } else {
+            let Some(end) = rest[i..].find('}') else {
+                Err(format!("unmatched '{{' in format string '{format}'"))?
+            };
+            let placeholder = &rest[i + 1..i + end];
+            let (argument, spec) = placeholder.split_once(':').unwrap_or((placeholder, ""));
+            if !argument.is_empty() {
+                Err(format!(
+                    "positional and named arguments are not supported, found '{{{placeholder}}}'"
+                ))?;
+            }
+            placeholders.push(format_spec(spec)?);
+            rest = &rest[i + end + 1..];
+        }
+    }
+
+    Ok(placeholders)
+}
+
+fn ident_arg(input: ParseStream) -> Result<Ident> {
+    let content;
+    parenthesized!(content in input);
+    content.parse()
+}
+
 fn function(input: ParseStream) -> Result<Output> {
     if input.peek(kw::boolean) {
         input.parse::<kw::boolean>()?;
@@ -105,6 +205,16 @@ fn function(input: ParseStream) -> Result<Output> {
     } else if input.peek(kw::ceiling) {
         input.parse::<kw::ceiling>()?;
         Ok(Output::Function(Function::Ceiling(Box::new(arg(input)?))))
+    } else if input.peek(kw::coalesce) {
+        let keyword: kw::coalesce = input.parse()?;
+        let args = arg_list(input)?;
+        if args.len() < 2 {
+            Err(syn::Error::new(
+                keyword.span,
+                "'coalesce' requires at least two arguments",
+            ))?;
+        }
+        Ok(Output::Function(Function::Coalesce(args)))
     } else if input.peek(kw::concat) {
         input.parse::<kw::concat>()?;
         let (arg1, arg2) = args(input)?;
@@ -112,12 +222,51 @@ fn function(input: ParseStream) -> Result<Output> {
             Box::new(arg1),
             Box::new(arg2),
         )))
+    } else if input.peek(kw::default) {
+        input.parse::<kw::default>()?;
+        let (arg, default) = args(input)?;
+        let Some(default) = literal(default) else {
+            Err(input.error("the second argument to 'default' must be a literal"))?
+        };
+        Ok(Output::Function(Function::Default(Box::new(arg), default)))
     } else if input.peek(kw::floor) {
         input.parse::<kw::floor>()?;
         Ok(Output::Function(Function::Floor(Box::new(arg(input)?))))
+    } else if input.peek(kw::format) {
+        let keyword: kw::format = input.parse()?;
+        let mut args = arg_list(input)?.into_iter();
+        let Some(Output::Literal(Value::String(format))) = args.next() else {
+            Err(syn::Error::new(
+                keyword.span,
+                "the first argument to 'format' must be a string literal",
+            ))?
+        };
+        let args: Vec<Output> = args.collect();
+        let placeholders =
+            format_placeholders(&format).map_err(|err| syn::Error::new(keyword.span, err))?;
+        if placeholders.len() != args.len() {
+            Err(syn::Error::new(
+                keyword.span,
+                format!(
+                    "format string has {} placeholder(s), found {} argument(s)",
+                    placeholders.len(),
+                    args.len()
+                ),
+            ))?;
+        }
+        Ok(Output::Function(Function::Format(format, args)))
     } else if input.peek(kw::integer) {
         input.parse::<kw::integer>()?;
         Ok(Output::Function(Function::Integer(Box::new(arg(input)?))))
+    } else if input.peek(kw::is_null) {
+        input.parse::<kw::is_null>()?;
+        Ok(Output::Function(Function::IsNull(Box::new(arg(input)?))))
+    } else if input.peek(kw::next) {
+        input.parse::<kw::next>()?;
+        Ok(Output::Function(Function::Next(ident_arg(input)?)))
+    } else if input.peek(kw::prev) {
+        input.parse::<kw::prev>()?;
+        Ok(Output::Function(Function::Prev(ident_arg(input)?)))
     } else if input.peek(kw::real) {
         input.parse::<kw::real>()?;
         Ok(Output::Function(Function::Real(Box::new(arg(input)?))))
@@ -141,7 +290,7 @@ fn unary(input: ParseStream) -> Result<Output> {
             right,
         })
     } else if input.peek(Token![!]) {
-        input.parse::<Token![-]>()?;
+        input.parse::<Token![!]>()?;
         let right = Box::new(unary(input)?);
         Ok(Output::Unary {
             operator: UnOp::Not,
@@ -201,14 +350,108 @@ impl Parse for Output {
     }
 }
 
-pub(crate) fn parse_output(input: &str) -> Output {
+/// Replaces calls to functions declared in `globals` with their declarations, and references to
+/// constants with their values.
+fn resolve(output: &mut Output, globals: &Globals) {
+    match output {
+        Output::Binary { left, right, .. } => {
+            resolve(left, globals);
+            resolve(right, globals);
+        }
+        Output::Function(function) => {
+            for arg in function.args_mut() {
+                resolve(arg, globals);
+            }
+            if let Function::Call(name, args) = function
+                && let Some(declaration) = globals.functions.get(name)
+            {
+                *function = Function::User(declaration.clone(), mem::take(args));
+            }
+        }
+        Output::Identifier(ident) => {
+            if let Some(value) = globals.constants.get(ident) {
+                *output = Output::Literal(value.clone());
+            }
+        }
+        Output::Unary { right, .. } => resolve(right, globals),
+        Output::Literal(_) => {}
+    }
+}
+
+fn parse_str(input: &str) -> Result<Output> {
     let tokens = match TokenStream::from_str(input) {
         Ok(stream) => stream,
         Err(e) => panic!("{e}"),
     };
 
-    match parse(tokens) {
-        Ok(output) => output,
+    parse(tokens)
+}
+
+pub(crate) fn parse_output(input: &str, globals: &Globals) -> Output {
+    match parse_str(input) {
+        Ok(mut output) => {
+            resolve(&mut output, globals);
+            output
+        }
         Err(e) => panic!("{e}"),
     }
 }
+
+/// Parses `input` as a name that can be referred to from expressions.
+pub(crate) fn parse_name(input: &str) -> Option<Ident> {
+    match parse_str(input) {
+        Ok(Output::Identifier(ident)) => Some(ident),
+        _ => None,
+    }
+}
+
+/// Checks whether `input` is the name of a built-in function.
+pub(crate) fn is_function_name(input: &str) -> bool {
+    FUNCTION_NAMES.contains(&input)
+}
+
+#[cfg(test)]
+#[allow(clippy::literal_string_with_formatting_args)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use crate::ColumnType;
+
+    fn format_type(format: &str, arg: Value) -> std::result::Result<ColumnType, String> {
+        Output::Function(Function::Format(
+            format.to_owned(),
+            vec![Output::Literal(arg)],
+        ))
+        .return_type(&HashMap::new())
+    }
+
+    #[test]
+    fn format_placeholders_are_checked() {
+        assert_eq!(
+            format_placeholders("{{{}}}-{:>08.2}-{:#x}").unwrap(),
+            ["", "", "x"]
+        );
+        assert!(format_placeholders("{0}").is_err());
+        assert!(format_placeholders("{name}").is_err());
+        assert!(format_placeholders("{:.*}").is_err());
+        assert!(format_placeholders("{:q}").is_err());
+        assert!(format_placeholders("{").is_err());
+        assert!(format_placeholders("}").is_err());
+    }
+
+    #[test]
+    fn format_conversions_are_type_checked() {
+        assert_eq!(
+            format_type("{:x}", Value::Integer(255)),
+            Ok(ColumnType::String)
+        );
+        assert_eq!(
+            format_type("{:e}", Value::Real(1.5)),
+            Ok(ColumnType::String)
+        );
+        assert!(format_type("{:x}", Value::Real(1.5)).is_err());
+        assert!(format_type("{:e}", Value::String(String::new())).is_err());
+    }
+}
@@ -0,0 +1,191 @@
+use regex_syntax::{
+    Parser,
+    hir::{Class, Hir, HirKind, Look},
+};
+
+/// The largest number of states that a compiled pattern may have.
+const MAX_STATES: usize = 100_000;
+
+/// An assertion about the position in the text that does not consume any characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Anchor {
+    Start,
+    End,
+    StartLine,
+    EndLine,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum State {
+    /// Consumes a character in one of the inclusive ranges, then moves to the given state.
+    Ranges(Vec<(char, char)>, usize),
+    Split(usize, usize),
+    Anchor(Anchor, usize),
+    Match,
+}
+
+/// A regular expression compiled to a non-deterministic automaton, which the generated code
+/// simulates without needing any external crates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Pattern {
+    /// The states of the automaton, starting from state 0.
+    pub(crate) states: Vec<State>,
+}
+
+impl Pattern {
+    /// Compiles `pattern`, which uses the syntax of the `regex` crate.
+    ///
+    /// # Errors
+    /// Returns an error if `pattern` is invalid, uses a feature that is not supported, or is too
+    /// large.
+    pub(crate) fn new(pattern: &str) -> Result<Pattern, String> {
+        let hir = Parser::new()
+            .parse(pattern)
+            .map_err(|err| err.to_string())?;
+
+        // The match state is compiled first, and the rest of the pattern is compiled backwards
+        // from it, so the start state is moved to the front afterwards.
+        let mut compiler = Compiler {
+            states: vec![State::Match],
+        };
+        let start = compiler.compile(&hir, 0)?;
+        let mut states = compiler.states;
+        let new_index = |index: usize| {
+            if index == start {
+                0
+            } else if index == 0 {
+                start
+            } else {
+                index
+            }
+        };
+        for state in &mut states {
+            match state {
+                State::Ranges(_, next) | State::Anchor(_, next) => *next = new_index(*next),
+                State::Split(first, second) => {
+                    *first = new_index(*first);
+                    *second = new_index(*second);
+                }
+                State::Match => {}
+            }
+        }
+        states.swap(0, start);
+
+        Ok(Pattern { states })
+    }
+}
+
+struct Compiler {
+    states: Vec<State>,
+}
+
+impl Compiler {
+    fn push(&mut self, state: State) -> Result<usize, String> {
+        if self.states.len() >= MAX_STATES {
+            Err("pattern is too large".to_string())?;
+        }
+        self.states.push(state);
+        Ok(self.states.len() - 1)
+    }
+
+    /// Compiles `hir` so that it continues to the state `next`, and returns its first state.
+    fn compile(&mut self, hir: &Hir, next: usize) -> Result<usize, String> {
+        match hir.kind() {
+            HirKind::Empty => Ok(next),
+            HirKind::Literal(literal) => {
+                let literal = str::from_utf8(&literal.0)
+                    .map_err(|_| "patterns must only match valid UTF-8".to_string())?;
+                let mut next = next;
+                for c in literal.chars().rev() {
+                    next = self.push(State::Ranges(vec![(c, c)], next))?;
+                }
+                Ok(next)
+            }
+            HirKind::Class(Class::Unicode(class)) => {
+                let ranges = class
+                    .ranges()
+                    .iter()
+                    .map(|range| (range.start(), range.end()))
+                    .collect();
+                self.push(State::Ranges(ranges, next))
+            }
+            HirKind::Class(Class::Bytes(_)) => {
+                Err("patterns must only match valid UTF-8".to_string())
+            }
+            HirKind::Look(look) => {
+                let anchor = match look {
+                    Look::Start => Anchor::Start,
+                    Look::End => Anchor::End,
+                    Look::StartLF => Anchor::StartLine,
+                    Look::EndLF => Anchor::EndLine,
+                    _ => Err("word boundaries and CRLF mode are not supported".to_string())?,
+                };
+                self.push(State::Anchor(anchor, next))
+            }
+            HirKind::Repetition(repetition) => {
+                let mut start = if let Some(max) = repetition.max {
+                    let mut start = next;
+                    for _ in repetition.min..max {
+                        let body = self.compile(&repetition.sub, start)?;
+                        start = self.push(State::Split(body, next))?;
+                    }
+                    start
+                } else {
+                    let split = self.push(State::Split(next, next))?;
+                    let body = self.compile(&repetition.sub, split)?;
+                    self.states[split] = State::Split(body, next);
+                    split
+                };
+                for _ in 0..repetition.min {
+                    start = self.compile(&repetition.sub, start)?;
+                }
+                Ok(start)
+            }
+            HirKind::Capture(capture) => self.compile(&capture.sub, next),
+            HirKind::Concat(hirs) => {
+                let mut next = next;
+                for hir in hirs.iter().rev() {
+                    next = self.compile(hir, next)?;
+                }
+                Ok(next)
+            }
+            HirKind::Alternation(hirs) => {
+                let (last, hirs) = hirs.split_last().expect("alternations are not empty");
+                let mut start = self.compile(last, next)?;
+                for hir in hirs.iter().rev() {
+                    let alternative = self.compile(hir, next)?;
+                    start = self.push(State::Split(alternative, start))?;
+                }
+                Ok(start)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn too_many_states() {
+        assert_eq!(
+            Pattern::new("a{100000}").unwrap_err(),
+            "pattern is too large"
+        );
+        assert_eq!(
+            Pattern::new("(?:a{1000}){1000}").unwrap_err(),
+            "pattern is too large"
+        );
+        assert!(Pattern::new("a{1000}").is_ok());
+    }
+
+    #[test]
+    fn unsupported_features() {
+        assert_eq!(
+            Pattern::new(r"\bword\b").unwrap_err(),
+            "word boundaries and CRLF mode are not supported"
+        );
+        assert!(Pattern::new(r"(?-u:\xFF)").is_err());
+        assert!(Pattern::new("(").is_err());
+    }
+}
@@ -1,8 +1,12 @@
 use crate::{
-    Aggregate, BinOp, Column, ColumnType, Function, OnInvalid, OnTitle, Output, Process, Program,
-    UnOp, Value,
+    Aggregate, BinOp, Column, ColumnType, Function, INTEGER_RANGE, LengthUnit, Monotonic,
+    OnDuplicate, OnError, OnInvalid, OnLongStreak, OnTitle, OnViolation, Outliers, Output, Process,
+    Program, Statistic, UnOp, Value,
+    pattern::{Anchor, Pattern, State},
 };
 
+use std::iter::zip;
+
 use proc_macro2::{Ident, Span, TokenStream};
 use quote::{ToTokens, quote};
 use syn::Index;
@@ -32,10 +36,37 @@ impl ToTokens for ColumnType {
     }
 }
 
+impl ToTokens for Pattern {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let states = self.states.iter().map(|state| match state {
+            State::Ranges(ranges, next) => {
+                let ranges = ranges.iter().map(|(start, end)| quote!((#start, #end)));
+                quote!(SanitisePatternState::Ranges(&[#(#ranges),*], #next))
+            }
+            State::Split(first, second) => quote!(SanitisePatternState::Split(#first, #second)),
+            State::Anchor(anchor, next) => {
+                let anchor = match anchor {
+                    Anchor::Start => quote!(Start),
+                    Anchor::End => quote!(End),
+                    Anchor::StartLine => quote!(StartLine),
+                    Anchor::EndLine => quote!(EndLine),
+                };
+                quote!(SanitisePatternState::Anchor(SanitiseAnchor::#anchor, #next))
+            }
+            State::Match => quote!(SanitisePatternState::Match),
+        });
+
+        tokens.extend(quote!(&[#(#states),*]));
+    }
+}
+
 impl ToTokens for Value {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         match self {
             Value::Boolean(b) => b.to_tokens(tokens),
+            // A negative literal is negated after any method call on it, and `i64::MIN` cannot
+            // be negated.
+            Value::Integer(i64::MIN) => tokens.extend(quote!(i64::MIN)),
             Value::Integer(i) => i.to_tokens(tokens),
             Value::Real(r) => r.to_tokens(tokens),
             Value::String(s) => s.to_tokens(tokens),
@@ -43,19 +74,85 @@ impl ToTokens for Value {
     }
 }
 
+/// An expression that evaluates to `None` rather than an error if it refers to a null value.
+struct OptionalOutput<'a>(&'a Output);
+
+impl ToTokens for OptionalOutput<'_> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let inner = match self.0 {
+            Output::Identifier(ident) => quote!(Ok(#ident.map(ToOwned::to_owned))),
+            Output::Function(function @ (Function::Next(ident) | Function::Prev(ident))) => {
+                let offset_ident = Ident::new(&format!("{function}_{ident}"), Span::call_site());
+                quote!(Ok(#offset_ident.map(ToOwned::to_owned)))
+            }
+            output => quote!((#output).map(Some)),
+        };
+
+        tokens.extend(inner);
+    }
+}
+
 impl ToTokens for Function {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let inner = match self {
             Function::Boolean(arg) => quote! { SanitiseConversions::to_bool(&((#arg)?)) },
             Function::Ceiling(arg) => quote! { Ok(sanitise_ceiling(&((#arg)?))) },
+            Function::Coalesce(args) => {
+                let (last, args) = args.split_last().expect("'coalesce' has arguments");
+                let mut inner = TokenStream::new();
+                for arg in args {
+                    let arg = OptionalOutput(arg);
+                    inner.extend(quote! {
+                        if let Some(coalesced) = (#arg)? {
+                            Ok(coalesced)
+                        } else
+                    });
+                }
+                quote!(#inner { #last })
+            }
             Function::Concat(arg1, arg2) => quote! {
                 Ok(sanitise_concat(&((#arg1)?), &((#arg2)?)))
             },
+            Function::Default(arg, default) => {
+                let arg = OptionalOutput(arg);
+                quote!(Ok(SanitiseDefault::sanitise_default((#arg)?, #default)))
+            }
             Function::Floor(arg) => quote! { Ok(sanitise_floor(&((#arg)?))) },
+            Function::Format(format, args) => {
+                let args = args.iter().map(|arg| quote!((#arg)?));
+                quote!(Ok(alloc::format!(#format, #(#args),*)))
+            }
             Function::Integer(arg) => quote! { SanitiseConversions::to_int(&((#arg)?)) },
+            Function::IsNull(arg) => {
+                let arg = OptionalOutput(arg);
+                quote!(Ok((#arg)?.is_none()))
+            }
+            Function::Next(ident) | Function::Prev(ident) => {
+                let offset_ident = Ident::new(&format!("{self}_{ident}"), Span::call_site());
+                let message = format!("'{self}({ident})' is null");
+                quote! {
+                    #offset_ident
+                        .map(ToOwned::to_owned)
+                        .ok_or_else(|| Interrupt::Error(#message.to_owned()))
+                }
+            }
             Function::Real(arg) => quote! { SanitiseConversions::to_float(&((#arg)?)) },
             Function::Round(arg) => quote! { Ok(sanitise_round(&((#arg)?))) },
             Function::String(arg) => quote! { SanitiseConversions::to_string(&((#arg)?)) },
+            Function::Call(name, _) => panic!("internal error: unresolved function '{name}'"),
+            Function::User(function, args) => {
+                let path = &function.path;
+                let args = zip(args, &function.args).map(|(arg, arg_type)| {
+                    let conversion = match arg_type {
+                        ColumnType::Bool => quote!(to_bool),
+                        ColumnType::Float => quote!(to_float),
+                        ColumnType::Integer => quote!(to_int),
+                        ColumnType::String => quote!(to_string),
+                    };
+                    quote!(SanitiseConversions::#conversion(&((#arg)?))?)
+                });
+                quote!(Ok(#path(#(#args),*)))
+            }
         };
 
         tokens.extend(inner);
@@ -100,18 +197,223 @@ impl ToTokens for Output {
                 left,
                 operator,
                 right,
-            } => tokens.extend(quote! { Ok(((#left)?) #operator ((#right)?)) }),
+            } => {
+                let operation = match operator {
+                    BinOp::Add => quote!(SanitiseArithmetic::sanitise_add(left, right)),
+                    BinOp::Sub => quote!(SanitiseArithmetic::sanitise_sub(left, right)),
+                    BinOp::Mul => quote!(SanitiseArithmetic::sanitise_mul(left, right)),
+                    BinOp::Div => quote!(SanitiseArithmetic::sanitise_div(left, right)),
+                    BinOp::Mod => quote!(SanitiseArithmetic::sanitise_rem(left, right)),
+                    _ => quote!(Ok(left #operator right)),
+                };
+                tokens.extend(quote! {
+                    {
+                        let (left, right) = SanitisePromote::promote((#left)?, (#right)?);
+                        #operation
+                    }
+                });
+            }
             Output::Function(function) => function.to_tokens(tokens),
             Output::Identifier(ident) => {
-                tokens.extend(quote!(Ok(#ident.unwrap().to_owned())));
+                let message = format!("'{ident}' is null");
+                tokens.extend(quote! {
+                    #ident
+                        .map(ToOwned::to_owned)
+                        .ok_or_else(|| Interrupt::Error(#message.to_owned()))
+                });
             }
-            Output::Literal(value) => tokens.extend(quote!(Ok(#value))),
+            Output::Literal(value) => tokens.extend(quote!(Ok(#value.to_owned()))),
+            Output::Unary {
+                operator: UnOp::Negate,
+                right,
+            } => tokens.extend(quote! { SanitiseArithmetic::sanitise_neg((#right)?) }),
             Output::Unary { operator, right } => tokens.extend(quote! { Ok(#operator((#right)?)) }),
         }
     }
 }
 
+/// Binds the identifiers that describe the current row, so that expressions can refer to them.
+fn context_bindings(columns: &[(Ident, ColumnType)]) -> TokenStream {
+    let mut bindings = quote! {
+        let row = Some(&context.row);
+        let line = Some(&context.line);
+        let section = Some(&context.section);
+    };
+    for (name, _) in columns {
+        for offset in ["", "prev_", "next_"] {
+            let raw = Ident::new(&format!("{offset}raw_{name}"), Span::call_site());
+            let value = Ident::new(&format!("{offset}value_{name}"), Span::call_site());
+            bindings.extend(quote! {
+                let #raw = context.#raw;
+                let #value = context.#raw;
+            });
+        }
+    }
+
+    bindings
+}
+
+/// Optional state kept by a column's automaton, and the code that maintains it.
+#[derive(Default)]
+struct AutomatonState {
+    /// The declarations of the fields that hold the state.
+    fields: TokenStream,
+    /// The initial values of the fields.
+    initialisation: TokenStream,
+    /// Runs at the start of each row, before anything is recorded.
+    reset: TokenStream,
+    /// Records the current row.
+    record: TokenStream,
+    /// Reverts the record of the current row, if it was made.
+    undo: TokenStream,
+}
+
+impl Column {
+    /// Gets the checks that compare each value with the last value that was accepted.
+    fn last_checks(&self) -> TokenStream {
+        let mut last_checks = TokenStream::new();
+
+        if let Some(monotonic) = self.monotonic {
+            let in_order = match monotonic {
+                Monotonic::NonDecreasing => quote!(value >= last),
+                Monotonic::StrictlyIncreasing => quote!(value > last),
+                Monotonic::NonIncreasing => quote!(value <= last),
+                Monotonic::StrictlyDecreasing => quote!(value < last),
+            };
+            last_checks.extend(quote! {
+                if !(#in_order) {
+                    return self.invalid(value);
+                }
+            });
+        }
+
+        match &self.max_change {
+            Some(Value::Integer(max_change)) => {
+                let max_change = max_change.unsigned_abs();
+                last_checks.extend(quote! {
+                    if last.abs_diff(*value) > #max_change {
+                        return self.invalid(value);
+                    }
+                });
+            }
+            Some(max_change) => last_checks.extend(quote! {
+                if !((value - last).abs() <= #max_change) {
+                    return self.invalid(value);
+                }
+            }),
+            None => {}
+        }
+
+        if let Some(max_change_ratio) = self.max_change_ratio {
+            let (value, last) = if self.input_type == ColumnType::Integer {
+                (quote!((*value as f64)), quote!((*last as f64)))
+            } else {
+                (quote!(value), quote!(last))
+            };
+            last_checks.extend(quote! {
+                if !((#value - #last).abs() <= #max_change_ratio * #last.abs()) {
+                    return self.invalid(value);
+                }
+            });
+        }
+
+        last_checks
+    }
+
+    /// Gets the number of valid values needed to end a series of missing values, and the code
+    /// that fills in that series, if the column uses an option that fills in missing values.
+    fn fill_invalid_streak(&self) -> Option<(usize, TokenStream)> {
+        let fraction = |by: &Option<Ident>| {
+            if by.is_some() {
+                quote! {
+                    let fraction = sanitise_fraction(
+                        start.checked_sub(1).and_then(|i| self.positions[i]),
+                        self.positions[start + i],
+                        self.positions[start + *missing],
+                        fraction,
+                    );
+                }
+            } else {
+                TokenStream::new()
+            }
+        };
+
+        match self.fill()? {
+            OnInvalid::Average(valid_streak) => Some((
+                *valid_streak,
+                quote! {
+                    let before_invalid_streak = self.output.last().unwrap_or(&valid_streak[0]);
+                    let after_invalid_streak = &valid_streak[0];
+                    let average = SanitiseArithmetic::sanitise_mean(&[
+                        before_invalid_streak.to_owned(),
+                        after_invalid_streak.to_owned(),
+                    ]);
+                    for _ in 0..(*missing) {
+                        self.output.push(average.to_owned())
+                    }
+                },
+            )),
+            OnInvalid::Interpolate(valid_streak, by) => {
+                let fraction = fraction(by);
+                Some((
+                    *valid_streak,
+                    quote! {
+                        let after_invalid_streak = valid_streak[0].to_owned();
+                        let before_invalid_streak = self
+                            .output
+                            .last()
+                            .unwrap_or(&after_invalid_streak)
+                            .to_owned();
+                        let start = self.output.len();
+                        for i in 0..(*missing) {
+                            let fraction = (i + 1) as f64 / (*missing + 1) as f64;
+                            #fraction
+                            self.output.push(SanitiseArithmetic::sanitise_interpolate(
+                                &before_invalid_streak,
+                                &after_invalid_streak,
+                                fraction,
+                            ));
+                        }
+                    },
+                ))
+            }
+            // Values halfway between the two valid values use the one before them.
+            OnInvalid::Nearest(by) => {
+                let fraction = fraction(by);
+                Some((
+                    1,
+                    quote! {
+                        let before_invalid_streak = self.output.last().cloned();
+                        let start = self.output.len();
+                        for i in 0..(*missing) {
+                            let fraction = (i + 1) as f64 / (*missing + 1) as f64;
+                            #fraction
+                            let nearest = match &before_invalid_streak {
+                                Some(before_invalid_streak) if fraction <= 0.5 => {
+                                    before_invalid_streak
+                                }
+                                _ => &valid_streak[0],
+                            };
+                            self.output.push(nearest.to_owned());
+                        }
+                    },
+                ))
+            }
+            OnInvalid::Next => Some((
+                1,
+                quote! {
+                    for _ in 0..(*missing) {
+                        self.output.push(valid_streak[0].to_owned());
+                    }
+                },
+            )),
+            _ => None,
+        }
+    }
+}
+
 impl ToTokens for Column {
+    #[allow(clippy::too_many_lines)]
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let title = format!("Column_{}", self.title);
         let name = Ident::new(&title, Span::call_site());
@@ -141,36 +443,88 @@ impl ToTokens for Column {
             (TokenStream::new(), TokenStream::new())
         };
 
+        let current = if self.provides_output {
+            AutomatonState {
+                fields: quote! { current: Option<#output_type>, },
+                initialisation: quote!(current: None,),
+                reset: quote!(self.current = None;),
+                record: quote!(self.current = self.output.last().cloned();),
+                undo: TokenStream::new(),
+            }
+        } else {
+            AutomatonState::default()
+        };
+        let record_current = &current.record;
+
+        // The last valid values used by `rolling`, and the value that was removed to make space
+        // for the value in the current row, so that the row can be undone.
+        let recent = if let Some((window, _)) = self.rolling {
+            AutomatonState {
+                fields: quote! {
+                    recent: VecDeque<#output_type>,
+                    evicted: Option<Option<#output_type>>,
+                },
+                initialisation: quote!(recent: VecDeque::with_capacity(#window), evicted: None,),
+                reset: quote!(self.evicted = None;),
+                record: quote! {
+                    let evicted = if self.recent.len() == #window {
+                        self.recent.pop_front()
+                    } else {
+                        None
+                    };
+                    self.recent.push_back(value.clone());
+                    self.evicted = Some(evicted);
+                },
+                undo: quote! {
+                    if let Some(evicted) = self.evicted.take() {
+                        self.recent.pop_back();
+                        if let Some(evicted) = evicted {
+                            self.recent.push_front(evicted);
+                        }
+                    }
+                },
+            }
+        } else {
+            AutomatonState::default()
+        };
+        let record_recent = &recent.record;
+        let statistic = match self.rolling {
+            Some((_, Statistic::Mean)) => quote!(sanitise_mean),
+            Some((_, Statistic::Median)) => quote!(sanitise_median),
+            None => TokenStream::new(),
+        };
+        let rolling = |sentinel: &Value| {
+            quote! {
+                let replacement = if self.recent.is_empty() {
+                    #sentinel.to_owned()
+                } else {
+                    SanitiseArithmetic::#statistic(self.recent.make_contiguous())
+                };
+                self.output.push(replacement);
+                #record_current
+                Ok(())
+            }
+        };
+
         let invalid_function = match &self.on_invalid {
             OnInvalid::Abort => {
                 let message = format!("invalid value for column '{}': {{}}", self.title);
                 quote!(Err(Interrupt::Error(format!(#message, value))))
             }
-            OnInvalid::Average(_) => quote! {
-                if let #state_name::Invalid { missing, valid_streak, last_action } = &mut self.state {
-                    *missing += 1;
-                    if !valid_streak.is_empty() {
-                        *missing += valid_streak.len();
-                        *valid_streak = vec![];
-                    }
-                    *last_action = Action::IncrementInvalid;
-                } else {
-                    self.state = #state_name::Invalid {
-                        missing: 1,
-                        valid_streak: vec![],
-                        last_action: Action::IncrementInvalid
-                    };
-                }
-
-                Ok(())
-            },
+            OnInvalid::Average(_)
+            | OnInvalid::Interpolate(_, _)
+            | OnInvalid::Nearest(_)
+            | OnInvalid::Next => quote!(self.missing()),
             OnInvalid::Delete => quote!(Err(Interrupt::Delete)),
             OnInvalid::Previous(sentinel) => quote! {
                 self.output.push(self.output.last().unwrap_or(&#sentinel).to_owned());
+                #record_current
                 Ok(())
             },
+            OnInvalid::Rolling(sentinel) => rolling(sentinel),
             OnInvalid::Sentinel(sentinel) => quote! {
                 self.output.push(#sentinel.to_owned());
+                #record_current
                 Ok(())
             },
         };
@@ -180,33 +534,101 @@ impl ToTokens for Column {
                 let message = format!("unexpected null in column '{}'", self.title);
                 quote!(Err(Interrupt::Error(#message.to_owned())))
             }
-            OnInvalid::Average(_) => quote! {
-                self.invalid(&#column_type::default())
-            },
+            OnInvalid::Average(_)
+            | OnInvalid::Interpolate(_, _)
+            | OnInvalid::Nearest(_)
+            | OnInvalid::Next => quote!(self.missing()),
             OnInvalid::Delete => quote!(Err(Interrupt::Delete)),
             OnInvalid::Previous(sentinel) => quote! {
                 self.output.push(self.output.last().unwrap_or(&#sentinel).to_owned());
+                #record_current
                 Ok(())
             },
+            OnInvalid::Rolling(sentinel) => rolling(sentinel),
             OnInvalid::Sentinel(sentinel) => quote! {
                 self.output.push(#sentinel.to_owned());
+                #record_current
                 Ok(())
             },
         };
 
         let mut push_function = TokenStream::new();
+        if self.clamp {
+            push_function.extend(quote!(let mut clamped = false;));
+        }
 
         if let Some(max) = &self.max {
+            push_function.extend(if self.clamp {
+                quote! {
+                    let clamped_max;
+                    let value = if value > &#max {
+                        clamped = true;
+                        clamped_max = #max.to_owned();
+                        &clamped_max
+                    } else {
+                        value
+                    };
+                }
+            } else {
+                quote! {
+                    if value > &#max {
+                        return self.invalid(value);
+                    }
+                }
+            });
+        }
+
+        if let Some(min) = &self.min {
+            push_function.extend(if self.clamp {
+                quote! {
+                    let clamped_min;
+                    let value = if value < &#min {
+                        clamped = true;
+                        clamped_min = #min.to_owned();
+                        &clamped_min
+                    } else {
+                        value
+                    };
+                }
+            } else {
+                quote! {
+                    if value < &#min {
+                        return self.invalid(value);
+                    }
+                }
+            });
+        }
+
+        if let Some(exclusive_max) = &self.exclusive_max {
             push_function.extend(quote! {
-                if value > &#max {
+                if value >= &#exclusive_max {
                     return self.invalid(value);
                 }
             });
         }
 
-        if let Some(min) = &self.min {
+        if let Some(exclusive_min) = &self.exclusive_min {
             push_function.extend(quote! {
-                if value < &#min {
+                if value <= &#exclusive_min {
+                    return self.invalid(value);
+                }
+            });
+        }
+
+        if let Some(ranges) = &self.ranges {
+            let in_range = ranges.iter().map(|range| match range {
+                (Some(low), Some(high)) => quote!((#low..=#high).contains(value)),
+                (Some(low), None) => quote!(value >= &#low),
+                (None, Some(high)) => quote!(value <= &#high),
+                (None, None) => quote!(true),
+            });
+            let in_any_range = if ranges.is_empty() {
+                quote!(false)
+            } else {
+                quote!(#(#in_range)||*)
+            };
+            push_function.extend(quote! {
+                if !(#in_any_range) {
                     return self.invalid(value);
                 }
             });
@@ -230,32 +652,188 @@ impl ToTokens for Column {
             });
         }
 
+        if self.min_length.is_some() || self.max_length.is_some() {
+            let length = match self.length_unit {
+                LengthUnit::Bytes => quote!(value.len()),
+                LengthUnit::Chars => quote!(value.chars().count()),
+            };
+            push_function.extend(quote!(let length = #length;));
+        }
+
+        if let Some(min_length) = self.min_length {
+            push_function.extend(quote! {
+                if length < #min_length {
+                    return self.invalid(value);
+                }
+            });
+        }
+
+        if let Some(max_length) = self.max_length {
+            push_function.extend(quote! {
+                if length > #max_length {
+                    return self.invalid(value);
+                }
+            });
+        }
+
+        if let Some(pattern) = &self.pattern {
+            push_function.extend(quote! {
+                if !sanitise_is_match(#pattern, value) {
+                    return self.invalid(value);
+                }
+            });
+        }
+
+        if let Some(invalid_pattern) = &self.invalid_pattern {
+            push_function.extend(quote! {
+                if sanitise_is_match(#invalid_pattern, value) {
+                    return self.invalid(value);
+                }
+            });
+        }
+
+        let last_checks = self.last_checks();
+
+        // The last value that was accepted, and the value that it replaced if it was accepted in
+        // the current row, so that the row can be undone.
+        let accepted = if last_checks.is_empty() {
+            AutomatonState::default()
+        } else {
+            push_function.extend(quote! {
+                if let Some(last) = &self.last_accepted {
+                    #last_checks
+                }
+            });
+            AutomatonState {
+                fields: quote! {
+                    last_accepted: Option<#column_type>,
+                    replaced: Option<Option<#column_type>>,
+                },
+                initialisation: quote!(last_accepted: None, replaced: None,),
+                reset: quote!(self.replaced = None;),
+                record: quote!(self.replaced = Some(self.last_accepted.replace(value.to_owned()));),
+                undo: quote! {
+                    if let Some(replaced) = self.replaced.take() {
+                        self.last_accepted = replaced;
+                    }
+                },
+            }
+        };
+        let record_accepted = &accepted.record;
+
         let output = &self.output;
 
+        let on_error = |part: &str| match self.on_error {
+            OnError::Abort => {
+                let message = format!("error in {part} of column '{}': {{}}", self.title);
+                quote!(Err(Interrupt::Error(
+                    format!(#message, interrupt.extract_error())
+                )))
+            }
+            OnError::Invalid => quote!(self.invalid(value)),
+        };
+        let output_error = on_error("output");
+
+        let title = Ident::new(&self.title, Span::call_site());
+        let prev_value = Ident::new(&format!("prev_raw_{title}"), Span::call_site());
+        let next_value = Ident::new(&format!("next_raw_{title}"), Span::call_site());
+        let mut bindings = context_bindings(&self.process_columns);
+        bindings.extend(quote! {
+            let prev_value = context.#prev_value;
+            let next_value = context.#next_value;
+        });
+        for variable in &self.variables {
+            bindings.extend(quote!(let #variable = Some(&variables.#variable);));
+        }
+
+        if let Some(valid_if) = &self.valid_if {
+            let valid_if_error = on_error("'valid-if'");
+            push_function.extend(quote! {
+                let valid = (|| -> Result<bool, Interrupt> {
+                    let value = Some(value);
+                    #bindings
+                    #valid_if
+                })();
+                match valid {
+                    Ok(true) => {}
+                    Ok(false) => return self.invalid(value),
+                    Err(interrupt) => return #valid_if_error,
+                }
+            });
+        }
+
+        // A clamped value replaces an invalid one, so it is not accepted or used by `rolling`, but
+        // it still ends a series of missing values, and is used to fill that series in.
+        let push_output = if self.clamp {
+            quote! {
+                if clamped {
+                    self.push_clamped(output)
+                } else {
+                    #record_accepted
+                    self.push_valid(output);
+                    Ok(())
+                }
+            }
+        } else {
+            quote! {
+                #record_accepted
+                self.push_valid(output);
+                Ok(())
+            }
+        };
+
         push_function.extend(quote! {
-            let value = Some(value);
-            self.push_valid((#output)?);
-            Ok(())
+            let output = (|| -> Result<#output_type, Interrupt> {
+                let value = Some(value);
+                #bindings
+                #output
+            })();
+            match output {
+                Ok(output) => {
+                    #push_output
+                }
+                Err(interrupt) => #output_error,
+            }
         });
 
-        let two = if self.output_type == ColumnType::Integer {
-            quote!(2)
+        // The positions of every row kept by the automaton, which are only needed to interpolate
+        // by another column. Rows that the automaton deletes are not recorded, so that the
+        // positions line up with the output.
+        let positions = if self.fill().and_then(OnInvalid::position_column).is_some() {
+            AutomatonState {
+                fields: quote! {
+                    position: Option<f64>,
+                    positions: Vec<Option<f64>>,
+                },
+                initialisation: quote!(position: None, positions: vec![],),
+                reset: TokenStream::new(),
+                record: quote!(self.positions.push(self.position);),
+                undo: quote!(self.positions.pop();),
+            }
         } else {
-            quote!(2.0)
+            AutomatonState::default()
+        };
+        let record_position = &positions.record;
+        let undo_position = &positions.undo;
+        let record_missing_position = |on_missing: &OnInvalid| {
+            if matches!(on_missing, OnInvalid::Abort | OnInvalid::Delete) {
+                TokenStream::new()
+            } else {
+                record_position.clone()
+            }
         };
+        let record_invalid_position = record_missing_position(&self.on_invalid);
+        let record_null_position = record_missing_position(&self.on_null);
 
-        let valid_function = if let OnInvalid::Average(valid_streak) = self.on_invalid {
+        let valid_function = if let Some((valid_streak, fill_invalid_streak)) =
+            self.fill_invalid_streak()
+        {
             quote! {
                 if let #state_name::Invalid { missing, valid_streak, last_action } = &mut self.state {
                     valid_streak.push(value);
                     *last_action = Action::AppendValid;
                     if valid_streak.len() >= #valid_streak {
-                        let before_invalid_streak = self.output.last().unwrap_or(&valid_streak[0]);
-                        let after_invalid_streak = &valid_streak[0];
-                        let average = (before_invalid_streak + after_invalid_streak) / &#two;
-                        for _ in 0..(*missing) {
-                            self.output.push(average.to_owned())
-                        }
+                        #fill_invalid_streak
                         self.output.extend(valid_streak.iter().cloned());
                         self.state = #state_name::Valid;
                     }
@@ -269,8 +847,122 @@ impl ToTokens for Column {
             }
         };
 
-        let undo_function = if let OnInvalid::Average(_) = self.on_invalid {
+        // The number of consecutive invalid values and null entries, and their values before the
+        // current row, so that the row can be undone.
+        let streaks = if self.max_invalid_streak.is_some() || self.max_null_streak.is_some() {
+            let long_streak = (self.on_long_streak == OnLongStreak::Delete)
+                .then(|| quote!(long_streak: Option<usize>,));
+            let new_long_streak =
+                (self.on_long_streak == OnLongStreak::Delete).then(|| quote!(long_streak: None,));
+            AutomatonState {
+                fields: quote! {
+                    invalid_streak: usize,
+                    null_streak: usize,
+                    previous_streaks: (usize, usize),
+                    #long_streak
+                },
+                initialisation: quote! {
+                    invalid_streak: 0, null_streak: 0, previous_streaks: (0, 0), #new_long_streak
+                },
+                reset: quote!(self.previous_streaks = (self.invalid_streak, self.null_streak);),
+                // A valid value ends both series.
+                record: quote! {
+                    self.invalid_streak = 0;
+                    self.null_streak = 0;
+                },
+                undo: quote!((self.invalid_streak, self.null_streak) = self.previous_streaks;),
+            }
+        } else {
+            AutomatonState::default()
+        };
+        let reset_on_valid = &streaks.record;
+        let check_streak = |streak: &Ident, other: &Ident, max: Option<usize>, kind: &str, undo| {
+            let Some(max) = max else {
+                return if streaks.fields.is_empty() {
+                    TokenStream::new()
+                } else {
+                    quote! {
+                        self.#streak += 1;
+                        self.#other = 0;
+                    }
+                };
+            };
+            let exceeded = match self.on_long_streak {
+                OnLongStreak::Abort => {
+                    let message = format!(
+                        "more than {max} consecutive {kind} in column '{}'",
+                        self.title
+                    );
+                    quote!(return Err(Interrupt::Error(#message.to_owned()));)
+                }
+                // The earlier rows in the series were kept, so they are removed by the process.
+                OnLongStreak::Delete => quote! {
+                    if self.#streak == #max + 1 {
+                        self.long_streak = Some(#max);
+                    }
+                    #undo
+                    return Err(Interrupt::Delete);
+                },
+            };
+            quote! {
+                self.#streak += 1;
+                self.#other = 0;
+                if self.#streak > #max {
+                    #exceeded
+                }
+            }
+        };
+        let invalid_streak = Ident::new("invalid_streak", Span::call_site());
+        let null_streak = Ident::new("null_streak", Span::call_site());
+        let check_clamped_streak = check_streak(
+            &invalid_streak,
+            &null_streak,
+            self.max_invalid_streak,
+            "invalid values",
+            undo_position.clone(),
+        );
+        // Rows that this column deletes are not part of its output, so they are not counted, and
+        // they do not end a series.
+        let check_invalid_streak = if self.on_invalid == OnInvalid::Delete {
+            TokenStream::new()
+        } else {
+            check_streak(
+                &invalid_streak,
+                &null_streak,
+                self.max_invalid_streak,
+                "invalid values",
+                if record_invalid_position.is_empty() {
+                    TokenStream::new()
+                } else {
+                    undo_position.clone()
+                },
+            )
+        };
+        let check_null_streak = if self.on_null == OnInvalid::Delete {
+            TokenStream::new()
+        } else {
+            check_streak(
+                &null_streak,
+                &invalid_streak,
+                self.max_null_streak,
+                "null entries",
+                if record_null_position.is_empty() {
+                    TokenStream::new()
+                } else {
+                    undo_position.clone()
+                },
+            )
+        };
+
+        let states = [&current, &accepted, &recent, &streaks, &positions];
+        let fields = states.map(|state| &state.fields);
+        let initialisation = states.map(|state| &state.initialisation);
+        let reset = states.map(|state| &state.reset);
+        let undo = states.map(|state| &state.undo);
+
+        let undo_function = if self.needs_state() {
             quote! {
+                #(#undo)*
                 if let #state_name::Invalid { missing, valid_streak, last_action } = &mut self.state {
                     if *last_action == Action::AppendValid {
                         valid_streak.pop();
@@ -285,22 +977,21 @@ impl ToTokens for Column {
                 }
             }
         } else {
-            quote!(self.output.pop();)
+            quote! {
+                #(#undo)*
+                self.output.pop();
+            }
         };
 
         let aggregate_function = match self.aggregate {
             Aggregate::Average => quote! {
-                let mut sum = self.output[end_index].clone();
-                for item in &self.output[start_index..end_index] {
-                    sum += item.clone();
-                }
-                sum / (end_index - start_index + 1) as #output_type
+                SanitiseArithmetic::sanitise_mean(&self.output[start_index..=end_index])
             },
             Aggregate::First => quote!(self.output[start_index].clone()),
             Aggregate::Last => quote!(self.output[end_index].clone()),
         };
 
-        let finish_function = if let OnInvalid::Average(_) = self.on_invalid {
+        let finish_function = if self.needs_state() {
             quote! {
                 if let #state_name::Invalid { missing, valid_streak, last_action } = &mut self.state {
                     *missing += valid_streak.len();
@@ -320,38 +1011,117 @@ impl ToTokens for Column {
             quote!(Ok(()))
         };
 
+        let set_current = if self.provides_output {
+            quote!(self.current = Some(value.clone());)
+        } else {
+            TokenStream::new()
+        };
+
+        let clamped_function = if self.clamp {
+            quote! {
+                fn push_clamped(&mut self, value: #output_type) -> Result<(), Interrupt> {
+                    #record_position
+                    #check_clamped_streak
+                    #set_current
+                    #valid_function
+                    Ok(())
+                }
+            }
+        } else {
+            TokenStream::new()
+        };
+
+        let missing_function = if self.needs_state() {
+            quote! {
+                fn missing(&mut self) -> Result<(), Interrupt> {
+                    if let #state_name::Invalid { missing, valid_streak, last_action } = &mut self.state {
+                        *missing += 1;
+                        if !valid_streak.is_empty() {
+                            *missing += valid_streak.len();
+                            *valid_streak = vec![];
+                        }
+                        *last_action = Action::IncrementInvalid;
+                    } else {
+                        self.state = #state_name::Invalid {
+                            missing: 1,
+                            valid_streak: vec![],
+                            last_action: Action::IncrementInvalid
+                        };
+                    }
+
+                    Ok(())
+                }
+            }
+        } else {
+            TokenStream::new()
+        };
+
+        let outlier_function = if self.outliers.is_some() {
+            quote! {
+                fn outlier(&mut self, value: &#column_type) -> Result<(), Interrupt> {
+                    #(#reset)*
+                    self.invalid(value)
+                }
+            }
+        } else {
+            TokenStream::new()
+        };
+
         let mut push_function_params = TokenStream::new();
-        for (name, column_type) in &self.process_columns {
-            push_function_params.extend(quote!(#name: Option<&#column_type>,));
+        for (name, output_type) in &self.previous_outputs {
+            push_function_params.extend(quote!(#name: Option<&#output_type>,));
         }
 
         tokens.extend(quote! {
             struct #name {
                 output: Vec<#output_type>,
+                #(#fields)*
                 #state
             }
 
             impl #name {
                 #[inline(always)]
                 fn new() -> #name {
-                    #name { output: vec![], #new_state }
+                    #name { output: vec![], #(#initialisation)* #new_state }
                 }
 
                 fn invalid(&mut self, value: &#column_type) -> Result<(), Interrupt> {
+                    #record_invalid_position
+                    #check_invalid_streak
                     #invalid_function
                 }
 
+                #missing_function
+
+                #outlier_function
+
                 fn null(&mut self) -> Result<(), Interrupt> {
+                    #(#reset)*
+                    #record_null_position
+                    #check_null_streak
                     #null_function
                 }
 
                 #[inline(always)]
                 fn push_valid(&mut self, value: #output_type) {
+                    #record_position
+                    #reset_on_valid
+                    #set_current
+                    #record_recent
                     #valid_function
                 }
 
+                #clamped_function
+
                 #[inline(always)]
-                fn push(&mut self, value: &#column_type, #push_function_params) -> Result<(), Interrupt> {
+                fn push(
+                    &mut self,
+                    value: &#column_type,
+                    context: &Context<'_>,
+                    variables: &Variables,
+                    #push_function_params
+                ) -> Result<(), Interrupt> {
+                    #(#reset)*
                     #push_function
                 }
 
@@ -373,6 +1143,7 @@ impl ToTokens for Column {
 }
 
 impl ToTokens for Process {
+    #[allow(clippy::too_many_lines)]
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let mut inner = TokenStream::new();
 
@@ -409,7 +1180,33 @@ impl ToTokens for Process {
             }
         }
 
+        // A row removed as part of a long streak is no longer a duplicate of later rows, and a row
+        // that it replaced with 'keep-last' is kept again.
+        let mut forget_row = TokenStream::new();
+        if self.columns.iter().any(Column::deletes_long_streaks) {
+            for (i, check) in self.duplicate_checks.iter().enumerate() {
+                let seen = Ident::new(&format!("seen_{i}"), Span::call_site());
+                forget_row.extend(if check.on_duplicate == OnDuplicate::KeepLast {
+                    quote! {
+                        if let Some(index) = replacements.remove(&(row, #i)) {
+                            removed.remove(&index);
+                            for value in #seen.values_mut() {
+                                if *value == row {
+                                    *value = index;
+                                }
+                            }
+                        } else {
+                            #seen.retain(|_, index| *index != row);
+                        }
+                    }
+                } else {
+                    quote!(#seen.retain(|_, index| *index != row);)
+                });
+            }
+        }
+
         let mut automata_feed = TokenStream::new();
+        let mut outlier_detection = TokenStream::new();
         let mut undo = TokenStream::new();
         let mut finish_automata = TokenStream::new();
         let mut get_returns = TokenStream::new();
@@ -417,13 +1214,14 @@ impl ToTokens for Process {
         let mut result_indexes = vec![];
         for (i, details) in automata_details.iter().enumerate() {
             let mut args = TokenStream::new();
-            for j in 0..automata_details.len() {
-                if i == j {
-                    continue;
-                }
-
-                let j = Index::from(j);
-                args.extend(quote!(file.#j[i].as_ref(),));
+            for (out_ident, _) in &self.columns[i].previous_outputs {
+                let j = self
+                    .columns
+                    .iter()
+                    .position(|column| *out_ident == format!("out_{}", column.title))
+                    .unwrap_or_else(|| panic!("internal error: invalid output - '{out_ident}'"));
+                let automaton_name = Ident::new(&format!("automaton_{j}"), Span::call_site());
+                args.extend(quote!(#automaton_name.current.as_ref(),));
             }
 
             if let Some((automaton_name, null_surrogate)) = details {
@@ -431,10 +1229,31 @@ impl ToTokens for Process {
 
                 let index = Index::from(i);
 
+                let remove_long_streak = if self.columns[i].deletes_long_streaks() {
+                    // Later rows are removed first, so a chain of replacements is undone in order.
+                    let forget_rows = (!forget_row.is_empty()).then(|| {
+                        quote! {
+                            for row in (start..kept_lines.len()).rev() {
+                                #forget_row
+                            }
+                        }
+                    });
+                    quote! {
+                        if let Some(length) = #automaton_name.long_streak.take() {
+                            let start = kept_lines.len().saturating_sub(length);
+                            #forget_rows
+                            removed.extend(start..kept_lines.len());
+                        }
+                    }
+                } else {
+                    TokenStream::new()
+                };
+
                 let push = quote! {
-                    if let Err(interrupt) = #automaton_name.push(tmp, #args) {
+                    if let Err(interrupt) = #automaton_name.push(tmp, &context, &variables, #args) {
                         match interrupt {
                             Interrupt::Delete => {
+                                #remove_long_streak
                                 #undo
                                 continue;
                             }
@@ -447,6 +1266,7 @@ impl ToTokens for Process {
                     if let Err(interrupt) = #automaton_name.null() {
                         match interrupt {
                             Interrupt::Delete => {
+                                #remove_long_streak
                                 #undo
                                 continue;
                             }
@@ -455,6 +1275,58 @@ impl ToTokens for Process {
                     }
                 };
 
+                let push = if let Some(outliers) = self.columns[i].outliers {
+                    let outliers_name = Ident::new(&format!("outliers_{i}"), Span::call_site());
+                    let detect = match outliers {
+                        Outliers::ZScore(threshold) => {
+                            quote!(sanitise_zscore_outliers(&values, #threshold))
+                        }
+                        Outliers::Iqr(threshold) => {
+                            quote!(sanitise_iqr_outliers(&values, #threshold))
+                        }
+                        Outliers::Hampel { window, threshold } => {
+                            quote!(sanitise_hampel_outliers(&values, #window, #threshold))
+                        }
+                    };
+                    let not_null = null_surrogate.as_ref().map(|surrogates| {
+                        let surrogates = ValueList(surrogates);
+                        quote!(.filter(|value| !#surrogates.contains(value)))
+                    });
+                    outlier_detection.extend(quote! {
+                        let #outliers_name = {
+                            let values: Vec<Option<f64>> = file.#index
+                                .iter()
+                                .map(|value| {
+                                    value
+                                        .as_ref()
+                                        #not_null
+                                        .and_then(|value| SanitiseConversions::to_float(value).ok())
+                                })
+                                .collect();
+                            #detect
+                        };
+                    });
+
+                    quote! {
+                        if #outliers_name[i] {
+                            if let Err(interrupt) = #automaton_name.outlier(tmp) {
+                                match interrupt {
+                                    Interrupt::Delete => {
+                                        #remove_long_streak
+                                        #undo
+                                        continue;
+                                    }
+                                    Interrupt::Error(s) => return Err((s, i + 1)),
+                                }
+                            }
+                        } else {
+                            #push
+                        }
+                    }
+                } else {
+                    push
+                };
+
                 let push = if let Some(surrogates) = null_surrogate {
                     let surrogates = ValueList(surrogates);
                     quote! {
@@ -468,6 +1340,22 @@ impl ToTokens for Process {
                     push
                 };
 
+                if let Some((by, _)) = self.columns[i].fill().and_then(OnInvalid::position_column) {
+                    let by_index = Index::from(
+                        self.column_names()
+                            .iter()
+                            .position(|name| name == by)
+                            .unwrap_or_else(|| {
+                                panic!("internal error: invalid interpolate-by target - '{by}'")
+                            }),
+                    );
+                    automata_feed.extend(quote! {
+                        #automaton_name.position = file.#by_index[i]
+                            .as_ref()
+                            .and_then(|value| SanitiseConversions::to_float(value).ok());
+                    });
+                }
+
                 automata_feed.extend(quote! {
                     if let Some(tmp) = &(file.#index)[i] {
                         #push
@@ -519,7 +1407,7 @@ impl ToTokens for Process {
                 ));
             }
 
-            let mut get_aggregates = TokenStream::new();
+            let mut get_aggregates = quote!(new_lines.push(kept_lines[start_index]););
             for (result_name, automaton_name, _, is_aggregate) in &new_result_names {
                 get_aggregates.extend(if *is_aggregate {
                     quote!(#result_name.push(run_value.to_owned());)
@@ -529,6 +1417,7 @@ impl ToTokens for Process {
             }
 
             get_returns.extend(quote! {
+                let mut new_lines = vec![];
                 let mut start_index = 0;
                 let mut run_value = &#aggregate_automaton_name.output[0];
                 for (i, current_value) in #aggregate_automaton_name.output.iter().enumerate() {
@@ -543,6 +1432,7 @@ impl ToTokens for Process {
             for (new_result_name, _, result_name, _) in &new_result_names {
                 get_returns.extend(quote!(let #result_name = #new_result_name;));
             }
+            get_returns.extend(quote!(let kept_lines = new_lines;));
         }
 
         let mut input_type = TokenStream::new();
@@ -553,6 +1443,205 @@ impl ToTokens for Process {
             parse_return.extend(quote!(Vec<Option<#column_type>>,));
         }
 
+        let mut context_fields = TokenStream::new();
+        let mut context_initialisation = TokenStream::new();
+        for (i, (name, column_type)) in zip(self.column_names(), self.column_types()).enumerate() {
+            let index = Index::from(i);
+            let raw = Ident::new(&format!("raw_{name}"), Span::call_site());
+            let prev_raw = Ident::new(&format!("prev_raw_{name}"), Span::call_site());
+            let next_raw = Ident::new(&format!("next_raw_{name}"), Span::call_site());
+            context_fields.extend(quote! {
+                #raw: Option<&'a #column_type>,
+                #prev_raw: Option<&'a #column_type>,
+                #next_raw: Option<&'a #column_type>,
+            });
+            // Entries that match a null surrogate are null in expressions too.
+            let not_null = self.columns[i].null_surrogates.as_ref().map(|surrogates| {
+                let surrogates = ValueList(surrogates);
+                quote!(.filter(|value| !#surrogates.contains(value)))
+            });
+            context_initialisation.extend(quote! {
+                #raw: file.#index[i].as_ref() #not_null,
+                #prev_raw: i.checked_sub(1).and_then(|i| file.#index[i].as_ref()) #not_null,
+                #next_raw: file.#index.get(i + 1).and_then(Option::as_ref) #not_null,
+            });
+        }
+
+        let mut variable_fields = TokenStream::new();
+        let mut variable_evaluation =
+            context_bindings(&zip(self.column_names(), self.column_types()).collect::<Vec<_>>());
+        let mut variable_initialisation = TokenStream::new();
+        for variable in &self.variables {
+            let name = &variable.name;
+            let output = &variable.output;
+            let output_type = variable.output_type;
+            let owned_name = Ident::new(&format!("variable_{name}"), Span::call_site());
+            let message = format!("error in variable '{name}': {{}}");
+            variable_fields.extend(quote!(#name: #output_type,));
+            variable_evaluation.extend(quote! {
+                let #owned_name = (|| -> Result<#output_type, Interrupt> { #output })()
+                    .map_err(|interrupt| {
+                        Interrupt::Error(format!(#message, interrupt.extract_error()))
+                    })?;
+                let #name = Some(&#owned_name);
+            });
+            variable_initialisation.extend(quote!(#name: #owned_name,));
+        }
+
+        let mut row_constraints = TokenStream::new();
+        let mut constraint_bindings =
+            context_bindings(&zip(self.column_names(), self.column_types()).collect::<Vec<_>>());
+        for variable in &self.variables {
+            let name = &variable.name;
+            constraint_bindings.extend(quote!(let #name = Some(&variables.#name);));
+        }
+        for row_constraint in &self.row_constraints {
+            let constraint = &row_constraint.constraint;
+            let source = &row_constraint.source;
+            let escaped_source = source.replace('{', "{{").replace('}', "}}");
+            let violation = match row_constraint.on_violation {
+                OnViolation::Abort => {
+                    let message = format!("row constraint '{source}' violated");
+                    quote!(return Err((#message.to_owned(), i + 1));)
+                }
+                OnViolation::Delete => quote!(continue;),
+                OnViolation::Warn => {
+                    let message =
+                        format!("warning: row constraint '{escaped_source}' violated on row {{}}");
+                    quote!(::std::eprintln!(#message, i + 1);)
+                }
+            };
+            let message = format!("error in row constraint '{escaped_source}': {{}}");
+            row_constraints.extend(quote! {
+                let satisfied = (|| -> Result<bool, Interrupt> {
+                    #constraint_bindings
+                    #constraint
+                })();
+                match satisfied {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        #violation
+                    }
+                    Err(interrupt) => {
+                        return Err((format!(#message, interrupt.extract_error()), i + 1));
+                    }
+                }
+            });
+        }
+
+        let column_names = self.column_names();
+        let mut duplicate_initialisation = TokenStream::new();
+        let mut duplicate_checks = TokenStream::new();
+        let mut record_keys = TokenStream::new();
+        let mut remove_rows = TokenStream::new();
+        for (i, check) in self.duplicate_checks.iter().enumerate() {
+            let seen = Ident::new(&format!("seen_{i}"), Span::call_site());
+            let duplicate_key = Ident::new(&format!("duplicate_key_{i}"), Span::call_site());
+            // Entries that match a null surrogate are null in keys too.
+            let values: Vec<TokenStream> = check
+                .columns
+                .iter()
+                .map(|column| {
+                    let position = column_names
+                        .iter()
+                        .position(|name| name == column)
+                        .unwrap_or_else(|| {
+                            panic!("internal error: invalid dedupe target - '{column}'")
+                        });
+                    let index = Index::from(position);
+                    let not_null =
+                        self.columns[position]
+                            .null_surrogates
+                            .as_ref()
+                            .map(|surrogates| {
+                                let surrogates = ValueList(surrogates);
+                                quote!(.filter(|value| !#surrogates.contains(value)))
+                            });
+                    quote!(file.#index[i].as_ref() #not_null)
+                })
+                .collect();
+            let key = if check.skip_nulls {
+                let value = &values[0];
+                quote!(#value.map(|value| (value.sanitise_key(),)))
+            } else {
+                quote!(Some((#(#values.map(SanitiseKey::sanitise_key),)*)))
+            };
+            let titles = check
+                .columns
+                .iter()
+                .map(|column| format!("'{column}'"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let (index, on_duplicate) = match check.on_duplicate {
+                OnDuplicate::Abort => {
+                    let message = if check.skip_nulls {
+                        format!("duplicate value in column {titles}")
+                    } else {
+                        format!("duplicate row in columns {titles}")
+                    };
+                    (quote!(_), quote!(return Err((#message.to_owned(), i + 1));))
+                }
+                OnDuplicate::DeleteLater => (quote!(_), quote!(continue;)),
+                OnDuplicate::KeepLast => (quote!(&index), quote!(replaced.push((#i, index));)),
+            };
+
+            duplicate_initialisation
+                .extend(quote!(let mut #seen = alloc::collections::BTreeMap::new();));
+            duplicate_checks.extend(quote! {
+                let #duplicate_key = #key;
+                if let Some(#index) = #duplicate_key.as_ref().and_then(|key| #seen.get(key)) {
+                    #on_duplicate
+                }
+            });
+            // Keys are only recorded once the row is kept, so rows deleted for any other reason
+            // are not treated as duplicates of later rows.
+            record_keys.extend(quote! {
+                if let Some(key) = #duplicate_key {
+                    #seen.insert(key, kept_lines.len() - 1);
+                }
+            });
+        }
+        let keep_last = self
+            .duplicate_checks
+            .iter()
+            .any(|check| check.on_duplicate == OnDuplicate::KeepLast);
+        if keep_last {
+            duplicate_checks = quote! {
+                let mut replaced = vec![];
+                #duplicate_checks
+            };
+            // Replacements are only needed to undo them when a long streak is removed.
+            record_keys.extend(if forget_row.is_empty() {
+                quote! {
+                    for (_, index) in replaced {
+                        removed.insert(index);
+                    }
+                }
+            } else {
+                quote! {
+                    for (check, index) in replaced {
+                        removed.insert(index);
+                        replacements.insert((kept_lines.len() - 1, check), index);
+                    }
+                }
+            });
+        }
+        // Rows that have already been kept are removed from every column after processing.
+        if keep_last || self.columns.iter().any(Column::deletes_long_streaks) {
+            duplicate_initialisation.extend(quote! {
+                let mut removed = alloc::collections::BTreeSet::new();
+            });
+            if keep_last && !forget_row.is_empty() {
+                duplicate_initialisation.extend(quote! {
+                    let mut replacements = alloc::collections::BTreeMap::new();
+                });
+            }
+            for (automaton_name, _) in automata_details.iter().flatten() {
+                remove_rows.extend(quote!(sanitise_remove(&mut #automaton_name.output, &removed);));
+            }
+            remove_rows.extend(quote!(sanitise_remove(&mut kept_lines, &removed);));
+        }
+
         let mut parse_function_declarations = TokenStream::new();
         let num_columns = automata_details.len();
         let mut parse_function_body = quote! {
@@ -579,26 +1668,74 @@ impl ToTokens for Process {
 
         let signature = self.signature();
         inner.extend(quote! {
-            pub(super) fn process(file: (#input_type)) -> Result<#signature, (String, usize)> {
+            struct Context<'a> {
+                row: i64,
+                line: i64,
+                section: i64,
+                #context_fields
+            }
+
+            struct Variables {
+                #variable_fields
+            }
+
+            impl Variables {
+                fn evaluate(context: &Context<'_>) -> Result<Variables, Interrupt> {
+                    #variable_evaluation
+                    Ok(Variables { #variable_initialisation })
+                }
+            }
+
+            pub(super) fn process(
+                file: (#input_type),
+                lines: &[usize],
+                section: usize,
+            ) -> Result<(#signature, Vec<usize>), (String, usize)> {
                 #automata_initialisation
+                #outlier_detection
+                #duplicate_initialisation
+                let mut kept_lines = vec![];
 
                 for i in 0..(file.0.len()) {
+                    let context = Context {
+                        row: i as i64,
+                        line: lines[i] as i64,
+                        section: section as i64,
+                        #context_initialisation
+                    };
+                    let variables = match Variables::evaluate(&context) {
+                        Ok(variables) => variables,
+                        Err(interrupt) => return Err((interrupt.extract_error(), i + 1)),
+                    };
+
+                    #row_constraints
+
+                    #duplicate_checks
+
                     #automata_feed
+
+                    kept_lines.push(lines[i]);
+                    #record_keys
                 }
 
                 #finish_automata
+                #remove_rows
                 #get_returns
 
-                Ok((#return_value))
+                Ok(((#return_value), kept_lines))
             }
-            pub(super) fn parse(file: &[Vec<&str>]) -> Result<(#parse_return), (String, usize)> {
+            pub(super) fn parse(
+                file: &[(usize, Vec<&str>)],
+            ) -> Result<((#parse_return), Vec<usize>), (String, usize)> {
                 #parse_function_declarations
+                let mut lines = vec![];
 
-                for (i, line) in file.iter().enumerate() {
+                for (i, (line_number, line)) in file.iter().enumerate() {
+                    lines.push(*line_number);
                     #parse_function_body
                 }
 
-                Ok((#parse_function_return))
+                Ok(((#parse_function_return), lines))
             }
         });
 
@@ -609,7 +1746,10 @@ impl ToTokens for Process {
     }
 }
 
+#[allow(clippy::too_many_lines)]
 fn runtime() -> TokenStream {
+    let (integer_min, integer_max) = (INTEGER_RANGE.start, INTEGER_RANGE.end);
+
     quote! {
         extern crate alloc;
         use ::core::prelude::rust_2021::*;
@@ -625,7 +1765,7 @@ fn runtime() -> TokenStream {
                 if let Interrupt::Error(message) = self {
                     message
                 } else {
-                    ::core::panic::panic!("attempted to extract error from 'Delete'")
+                    ::core::panic!("attempted to extract error from 'Delete'")
                 }
             }
         }
@@ -684,9 +1824,14 @@ fn runtime() -> TokenStream {
                 Ok(*self)
             }
 
-            #[inline(always)]
             fn to_int(&self) -> Result<i64, Interrupt> {
-                Ok(*self as i64)
+                let floor = self.floor();
+                if (#integer_min..#integer_max).contains(&floor) {
+                    Ok(floor as i64)
+                } else {
+                    let message = format!("cannot convert {self} to an integer");
+                    Err(Interrupt::Error(message))
+                }
             }
 
             #[inline(always)]
@@ -747,6 +1892,218 @@ fn runtime() -> TokenStream {
             }
         }
 
+        trait SanitisePromote<Rhs> {
+            type Output;
+
+            fn promote(self, rhs: Rhs) -> (Self::Output, Self::Output);
+        }
+
+        macro_rules! sanitise_promote_identity {
+            ($($t:ty),*) => {
+                $(
+                    impl SanitisePromote<$t> for $t {
+                        type Output = $t;
+
+                        #[inline(always)]
+                        fn promote(self, rhs: $t) -> ($t, $t) {
+                            (self, rhs)
+                        }
+                    }
+                )*
+            };
+        }
+
+        sanitise_promote_identity!(bool, f64, i64, String);
+
+        impl SanitisePromote<f64> for i64 {
+            type Output = f64;
+
+            #[inline(always)]
+            fn promote(self, rhs: f64) -> (f64, f64) {
+                (self as f64, rhs)
+            }
+        }
+
+        impl SanitisePromote<i64> for f64 {
+            type Output = f64;
+
+            #[inline(always)]
+            fn promote(self, rhs: i64) -> (f64, f64) {
+                (self, rhs as f64)
+            }
+        }
+
+        trait SanitiseDefault<T> {
+            type Output;
+
+            fn sanitise_default(self, default: T) -> Self::Output;
+        }
+
+        macro_rules! sanitise_default_identity {
+            ($($t:ty),*) => {
+                $(
+                    impl SanitiseDefault<$t> for Option<$t> {
+                        type Output = $t;
+
+                        #[inline(always)]
+                        fn sanitise_default(self, default: $t) -> $t {
+                            self.unwrap_or(default)
+                        }
+                    }
+                )*
+            };
+        }
+
+        sanitise_default_identity!(bool, f64, i64);
+
+        impl SanitiseDefault<&str> for Option<String> {
+            type Output = String;
+
+            #[inline(always)]
+            fn sanitise_default(self, default: &str) -> String {
+                self.unwrap_or_else(|| default.to_owned())
+            }
+        }
+
+        impl SanitiseDefault<i64> for Option<f64> {
+            type Output = f64;
+
+            #[inline(always)]
+            fn sanitise_default(self, default: i64) -> f64 {
+                self.unwrap_or(default as f64)
+            }
+        }
+
+        trait SanitiseArithmetic: Sized {
+            fn sanitise_add(self, rhs: Self) -> Result<Self, Interrupt>;
+            fn sanitise_sub(self, rhs: Self) -> Result<Self, Interrupt>;
+            fn sanitise_mul(self, rhs: Self) -> Result<Self, Interrupt>;
+            fn sanitise_div(self, rhs: Self) -> Result<Self, Interrupt>;
+            fn sanitise_rem(self, rhs: Self) -> Result<Self, Interrupt>;
+            fn sanitise_neg(self) -> Result<Self, Interrupt>;
+            fn sanitise_mean(values: &[Self]) -> Self;
+            fn sanitise_median(values: &[Self]) -> Self;
+            fn sanitise_interpolate(before: &Self, after: &Self, fraction: f64) -> Self;
+        }
+
+        impl SanitiseArithmetic for i64 {
+            #[inline(always)]
+            fn sanitise_add(self, rhs: i64) -> Result<i64, Interrupt> {
+                self.checked_add(rhs)
+                    .ok_or_else(|| Interrupt::Error(format!("overflow in {self} + {rhs}")))
+            }
+
+            #[inline(always)]
+            fn sanitise_sub(self, rhs: i64) -> Result<i64, Interrupt> {
+                self.checked_sub(rhs)
+                    .ok_or_else(|| Interrupt::Error(format!("overflow in {self} - {rhs}")))
+            }
+
+            #[inline(always)]
+            fn sanitise_mul(self, rhs: i64) -> Result<i64, Interrupt> {
+                self.checked_mul(rhs)
+                    .ok_or_else(|| Interrupt::Error(format!("overflow in {self} * {rhs}")))
+            }
+
+            fn sanitise_div(self, rhs: i64) -> Result<i64, Interrupt> {
+                if rhs == 0 {
+                    Err(Interrupt::Error(format!("division by zero in {self} / {rhs}")))
+                } else {
+                    self.checked_div(rhs)
+                        .ok_or_else(|| Interrupt::Error(format!("overflow in {self} / {rhs}")))
+                }
+            }
+
+            fn sanitise_rem(self, rhs: i64) -> Result<i64, Interrupt> {
+                if rhs == 0 {
+                    Err(Interrupt::Error(format!("division by zero in {self} % {rhs}")))
+                } else {
+                    self.checked_rem(rhs)
+                        .ok_or_else(|| Interrupt::Error(format!("overflow in {self} % {rhs}")))
+                }
+            }
+
+            #[inline(always)]
+            fn sanitise_neg(self) -> Result<i64, Interrupt> {
+                self.checked_neg()
+                    .ok_or_else(|| Interrupt::Error(format!("overflow in -{self}")))
+            }
+
+            fn sanitise_mean(values: &[i64]) -> i64 {
+                // The sum of fewer than 2^64 values cannot overflow an i128,
+                // and the mean of a set of i64 values always fits in an i64.
+                let sum: i128 = values.iter().map(|&value| i128::from(value)).sum();
+                (sum / values.len() as i128) as i64
+            }
+
+            fn sanitise_median(values: &[i64]) -> i64 {
+                let mut sorted = values.to_vec();
+                sorted.sort_unstable();
+                let middle = sorted.len() / 2;
+                if sorted.len() % 2 == 0 {
+                    SanitiseArithmetic::sanitise_mean(&sorted[middle - 1..=middle])
+                } else {
+                    sorted[middle]
+                }
+            }
+
+            fn sanitise_interpolate(before: &i64, after: &i64, fraction: f64) -> i64 {
+                let before = *before as f64;
+                (before + (*after as f64 - before) * fraction).round() as i64
+            }
+        }
+
+        impl SanitiseArithmetic for f64 {
+            #[inline(always)]
+            fn sanitise_add(self, rhs: f64) -> Result<f64, Interrupt> {
+                Ok(self + rhs)
+            }
+
+            #[inline(always)]
+            fn sanitise_sub(self, rhs: f64) -> Result<f64, Interrupt> {
+                Ok(self - rhs)
+            }
+
+            #[inline(always)]
+            fn sanitise_mul(self, rhs: f64) -> Result<f64, Interrupt> {
+                Ok(self * rhs)
+            }
+
+            #[inline(always)]
+            fn sanitise_div(self, rhs: f64) -> Result<f64, Interrupt> {
+                Ok(self / rhs)
+            }
+
+            #[inline(always)]
+            fn sanitise_rem(self, rhs: f64) -> Result<f64, Interrupt> {
+                Ok(self % rhs)
+            }
+
+            #[inline(always)]
+            fn sanitise_neg(self) -> Result<f64, Interrupt> {
+                Ok(-self)
+            }
+
+            fn sanitise_mean(values: &[f64]) -> f64 {
+                values.iter().sum::<f64>() / values.len() as f64
+            }
+
+            fn sanitise_median(values: &[f64]) -> f64 {
+                let mut sorted = values.to_vec();
+                sorted.sort_by(f64::total_cmp);
+                let middle = sorted.len() / 2;
+                if sorted.len() % 2 == 0 {
+                    (sorted[middle - 1] + sorted[middle]) / 2.0
+                } else {
+                    sorted[middle]
+                }
+            }
+
+            fn sanitise_interpolate(before: &f64, after: &f64, fraction: f64) -> f64 {
+                before + (after - before) * fraction
+            }
+        }
+
         #[inline(always)]
         fn sanitise_ceiling(value: &f64) -> f64 {
             (*value).ceil()
@@ -762,6 +2119,240 @@ fn runtime() -> TokenStream {
             (*value).round()
         }
 
+        enum SanitiseAnchor {
+            Start,
+            End,
+            StartLine,
+            EndLine,
+        }
+
+        enum SanitisePatternState {
+            Ranges(&'static [(char, char)], usize),
+            Split(usize, usize),
+            Anchor(SanitiseAnchor, usize),
+            Match,
+        }
+
+        /// Checks whether `pattern` matches any part of `text`.
+        fn sanitise_is_match(pattern: &[SanitisePatternState], text: &str) -> bool {
+            // The states reached by consuming the previous character, and the position at which
+            // each state was last visited, so that each state is only visited once per position.
+            let mut current = vec![];
+            let mut visited = vec![usize::MAX; pattern.len()];
+            let mut waiting = vec![];
+            let mut previous = None;
+            let mut chars = text.chars();
+            let mut next_char = chars.next();
+            let mut position = 0;
+
+            loop {
+                current.push(0);
+                waiting.clear();
+                while let Some(state) = current.pop() {
+                    if visited[state] == position {
+                        continue;
+                    }
+                    visited[state] = position;
+
+                    match &pattern[state] {
+                        SanitisePatternState::Ranges(_, _) => waiting.push(state),
+                        SanitisePatternState::Split(first, second) => {
+                            current.push(*second);
+                            current.push(*first);
+                        }
+                        SanitisePatternState::Anchor(anchor, next) => {
+                            let holds = match anchor {
+                                SanitiseAnchor::Start => previous.is_none(),
+                                SanitiseAnchor::End => next_char.is_none(),
+                                SanitiseAnchor::StartLine => matches!(previous, None | Some('\n')),
+                                SanitiseAnchor::EndLine => matches!(next_char, None | Some('\n')),
+                            };
+                            if holds {
+                                current.push(*next);
+                            }
+                        }
+                        SanitisePatternState::Match => return true,
+                    }
+                }
+
+                let Some(c) = next_char else {
+                    return false;
+                };
+                for &state in &waiting {
+                    if let SanitisePatternState::Ranges(ranges, next) = &pattern[state] {
+                        if ranges.iter().any(|&(start, end)| start <= c && c <= end) {
+                            current.push(*next);
+                        }
+                    }
+                }
+
+                previous = Some(c);
+                next_char = chars.next();
+                position += 1;
+            }
+        }
+
+        /// Converts values into keys that can be stored in a `BTreeMap`.
+        trait SanitiseKey {
+            type Key: Ord;
+
+            fn sanitise_key(&self) -> Self::Key;
+        }
+
+        impl SanitiseKey for bool {
+            type Key = bool;
+
+            #[inline(always)]
+            fn sanitise_key(&self) -> bool {
+                *self
+            }
+        }
+
+        impl SanitiseKey for f64 {
+            type Key = u64;
+
+            #[inline(always)]
+            fn sanitise_key(&self) -> u64 {
+                // 0.0 and -0.0 are equal, but have different bits.
+                if *self == 0.0 { 0 } else { self.to_bits() }
+            }
+        }
+
+        impl SanitiseKey for i64 {
+            type Key = i64;
+
+            #[inline(always)]
+            fn sanitise_key(&self) -> i64 {
+                *self
+            }
+        }
+
+        impl SanitiseKey for String {
+            type Key = String;
+
+            #[inline(always)]
+            fn sanitise_key(&self) -> String {
+                self.to_owned()
+            }
+        }
+
+        /// Removes the values at each index in `removed` from `values`.
+        fn sanitise_remove<T>(values: &mut Vec<T>, removed: &alloc::collections::BTreeSet<usize>) {
+            let mut index = 0;
+            values.retain(|_| {
+                let keep = !removed.contains(&index);
+                index += 1;
+                keep
+            });
+        }
+
+        /// Gets how far `current` is from `before` towards `after`, or `fallback` if any of the
+        /// positions are null or the distance is not finite.
+        fn sanitise_fraction(
+            before: Option<f64>,
+            current: Option<f64>,
+            after: Option<f64>,
+            fallback: f64,
+        ) -> f64 {
+            if let (Some(before), Some(current), Some(after)) = (before, current, after) {
+                let fraction = (current - before) / (after - before);
+                if fraction.is_finite() {
+                    return fraction.clamp(0.0, 1.0);
+                }
+            }
+            fallback
+        }
+
+        /// Sorts the finite values in `values`.
+        fn sanitise_sorted_finite(values: impl Iterator<Item = f64>) -> Vec<f64> {
+            let mut sorted: Vec<f64> = values.filter(|value| value.is_finite()).collect();
+            sorted.sort_by(f64::total_cmp);
+            sorted
+        }
+
+        /// Gets the quantile `q` of `sorted`, which must not be empty, interpolating linearly
+        /// between values.
+        fn sanitise_quantile(sorted: &[f64], q: f64) -> f64 {
+            let position = q * (sorted.len() - 1) as f64;
+            let lower = position as usize;
+            let fraction = position - lower as f64;
+            match sorted.get(lower + 1) {
+                Some(upper) => sorted[lower] + fraction * (upper - sorted[lower]),
+                None => sorted[lower],
+            }
+        }
+
+        /// Finds the values that are more than `threshold` standard deviations from the mean.
+        fn sanitise_zscore_outliers(values: &[Option<f64>], threshold: f64) -> Vec<bool> {
+            let finite = sanitise_sorted_finite(values.iter().flatten().copied());
+            if finite.is_empty() {
+                return vec![false; values.len()];
+            }
+            let mean = finite.iter().sum::<f64>() / finite.len() as f64;
+            let variance = finite
+                .iter()
+                .map(|value| (value - mean) * (value - mean))
+                .sum::<f64>()
+                / finite.len() as f64;
+            // Comparing squares avoids needing a square root, which is not available in `core`.
+            values
+                .iter()
+                .map(|value| {
+                    value.is_some_and(|value| {
+                        value.is_finite()
+                            && (value - mean) * (value - mean) > threshold * threshold * variance
+                    })
+                })
+                .collect()
+        }
+
+        /// Finds the values that are more than `threshold` interquartile ranges below the lower
+        /// quartile or above the upper quartile.
+        fn sanitise_iqr_outliers(values: &[Option<f64>], threshold: f64) -> Vec<bool> {
+            let sorted = sanitise_sorted_finite(values.iter().flatten().copied());
+            if sorted.is_empty() {
+                return vec![false; values.len()];
+            }
+            let lower = sanitise_quantile(&sorted, 0.25);
+            let upper = sanitise_quantile(&sorted, 0.75);
+            let range = upper - lower;
+            values
+                .iter()
+                .map(|value| {
+                    value.is_some_and(|value| {
+                        value < lower - threshold * range || value > upper + threshold * range
+                    })
+                })
+                .collect()
+        }
+
+        /// Finds the values that are more than `threshold` scaled median absolute deviations
+        /// from the median of the values up to `window` rows before and after them.
+        fn sanitise_hampel_outliers(
+            values: &[Option<f64>],
+            window: usize,
+            threshold: f64,
+        ) -> Vec<bool> {
+            (0..values.len())
+                .map(|i| {
+                    let value = match values[i] {
+                        Some(value) if value.is_finite() => value,
+                        _ => return false,
+                    };
+                    let start = i.saturating_sub(window);
+                    let end = (i + window + 1).min(values.len());
+                    let nearby = sanitise_sorted_finite(values[start..end].iter().flatten().copied());
+                    let median = sanitise_quantile(&nearby, 0.5);
+                    let deviations =
+                        sanitise_sorted_finite(nearby.iter().map(|nearby| (nearby - median).abs()));
+                    // Scales the median absolute deviation to estimate the standard deviation of
+                    // normally distributed values.
+                    let deviation = 1.4826 * sanitise_quantile(&deviations, 0.5);
+                    (value - median).abs() > threshold * deviation
+                })
+                .collect()
+        }
+
         #[inline(always)]
         fn sanitise_concat(value1: &str, value2: &str) -> String {
             let mut output = String::with_capacity(value1.len() + value2.len());
@@ -773,6 +2364,7 @@ fn runtime() -> TokenStream {
 }
 
 impl ToTokens for Program {
+    #[allow(clippy::too_many_lines)]
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let csv = &self.csv;
 
@@ -786,7 +2378,7 @@ impl ToTokens for Program {
         let mut inner = TokenStream::new();
 
         let process_function_input_type = if self.string_input {
-            quote!(&[Vec<&str>])
+            quote!(&[(usize, Vec<&str>)])
         } else {
             let mut file_type = TokenStream::new();
 
@@ -849,11 +2441,12 @@ impl ToTokens for Program {
 
         if self.string_input {
             process_function.extend(quote! {
-                let (#initial_assignment_target) = parse_file(file)?;
+                let ((#initial_assignment_target), lines) = parse_file(file)?;
             });
         } else {
             process_function.extend(quote! {
                 let (#initial_assignment_target) = file;
+                let lines: Vec<usize> = (1..=item_0.len()).collect();
             });
         }
 
@@ -874,8 +2467,9 @@ impl ToTokens for Program {
                     returns.extend(quote!(#column_name,));
                 }
             }
-            process_function
-                .extend(quote! { let (#assignment_target) = #process_name::process(#args)?; });
+            process_function.extend(quote! {
+                let ((#assignment_target), lines) = #process_name::process(#args, &lines, section)?;
+            });
 
             args = quote!((#inputs));
 
@@ -903,17 +2497,21 @@ impl ToTokens for Program {
                 for file_section in files.iter_mut() {
                     file.append(file_section);
                 }
-                let result = process(&file);
+                let result = process(&file, 0);
             },
             OnTitle::Once => quote! {
                 if files.len() > 2 {
                     return Err(("Found extra set of headers".to_owned(), files[1].len() + 1))
                 }
 
-                let result = process(&files[1]);
+                let result = process(&files[1], 0);
             },
             OnTitle::Split => quote! {
-                let result = files[1..].iter().map(|file| process(file)).collect();
+                let result = files[1..]
+                    .iter()
+                    .enumerate()
+                    .map(|(section, file)| process(file, section))
+                    .collect();
             },
         };
 
@@ -939,7 +2537,7 @@ impl ToTokens for Program {
             }
         } else {
             quote! {
-                let result = process(csv);
+                let result = process(csv, 0);
             }
         };
 
@@ -953,25 +2551,27 @@ impl ToTokens for Program {
         let main_return = quote!(result);
 
         inner.extend(quote! {
-            fn get_files(csv: &str) -> Vec<Vec<Vec<&str>>> {
-                let mut lines: Vec<&str> = csv
+            fn get_files(csv: &str) -> Vec<Vec<(usize, Vec<&str>)>> {
+                let mut lines: Vec<(usize, &str)> = csv
                     .split('\n')
                     .map(|s| {
                         s.strip_suffix("\r")
                             .unwrap_or(s)
                     })
+                    .enumerate()
+                    .map(|(i, line)| (i + 1, line))
                     .collect();
-                if lines.last().is_some_and(|line| line.is_empty()) {
+                if lines.last().is_some_and(|(_, line)| line.is_empty()) {
                     lines.pop();
                 }
 
                 lines
-                    .split(|&line| line == #header)
+                    .split(|&(_, line)| line == #header)
                     .map(|file| {
                         file
                             .iter()
-                            .map(|line| {
-                                line.split(',').collect()
+                            .map(|&(line_number, line)| {
+                                (line_number, line.split(',').collect())
                             })
                             .collect()
                     })
@@ -979,7 +2579,10 @@ impl ToTokens for Program {
             }
 
             #[inline(always)]
-            fn process(file: #process_function_input_type) -> Result<#signature, (String, usize)> {
+            fn process(
+                file: #process_function_input_type,
+                section: usize,
+            ) -> Result<#signature, (String, usize)> {
                 #process_function
 
                 Ok((#process_function_return))
@@ -996,6 +2599,7 @@ impl ToTokens for Program {
         });
 
         tokens.extend(quote! { {
+            #[allow(clippy::pedantic, clippy::nursery, clippy::ptr_arg)]
             mod __sanitise { #inner }
             __sanitise::main(#csv)
         } });
@@ -0,0 +1,77 @@
+//! Checks exclusive bounds and ranges.
+
+use sanitise::sanitise;
+
+#[test]
+fn real_exclusive_bounds_on_integers() {
+    let a = [-3, -2, -1, 2, 3].map(Some);
+    let ((a, b),) = sanitise!(
+        r#"
+            processes:
+              - name: bounds
+                columns:
+                  - title: a
+                    column-type: integer
+                    exclusive-min: -2.5
+                    exclusive-max: 2.5
+                    on-invalid: sentinel
+                    invalid-sentinel: 0
+                  - title: b
+                    column-type: integer
+                    exclusive-min: -2.0
+                    exclusive-max: 3.0
+                    on-invalid: sentinel
+                    invalid-sentinel: 0
+        "#,
+        (&a, &a),
+    )
+    .unwrap();
+
+    assert_eq!(a, [0, -2, -1, 2, 0]);
+    assert_eq!(b, [0, 0, -1, 2, 0]);
+}
+
+#[test]
+fn ranges() {
+    let a = [30, 40, 120, 155, 999, 1000].map(Some);
+    let ((a,),) = sanitise!(
+        r#"
+            processes:
+              - name: ranges
+                columns:
+                  - title: a
+                    column-type: integer
+                    ranges: [[40, 100.5], [150, 160], [1000, ~]]
+                    on-invalid: sentinel
+                    invalid-sentinel: 0
+        "#,
+        (&a,),
+    )
+    .unwrap();
+
+    assert_eq!(a, [0, 40, 0, 155, 0, 1000]);
+}
+
+#[test]
+fn clamp() {
+    let a = [-5, 5, 7, 15].map(Some);
+    let ((a,),) = sanitise!(
+        r#"
+            processes:
+              - name: clamp
+                columns:
+                  - title: a
+                    column-type: integer
+                    min: 0
+                    max: 10
+                    invalid-values: [7]
+                    on-invalid: clamp
+                    clamp-fallback: sentinel
+                    invalid-sentinel: -1
+        "#,
+        (&a,),
+    )
+    .unwrap();
+
+    assert_eq!(a, [0, 5, -1, 10]);
+}
@@ -0,0 +1,61 @@
+//! Checks how root-level constants are resolved.
+
+use sanitise::sanitise;
+
+#[test]
+fn constants_in_values_and_expressions() {
+    let pulse = [50, 250, 100].map(Some);
+    let ((pulse, scaled),) = sanitise!(
+        r#"
+            constants:
+              max_pulse:
+                type: integer
+                value: 220
+              fallback:
+                type: integer
+                value: "max_pulse / 10"
+            processes:
+              - name: constants
+                columns:
+                  - title: pulse
+                    column-type: integer
+                    max: max_pulse
+                    on-invalid: sentinel
+                    invalid-sentinel: fallback
+                  - title: scaled
+                    column-type: integer
+                    output: value * 100 / max_pulse
+        "#,
+        (&pulse, &pulse),
+    )
+    .unwrap();
+
+    assert_eq!(pulse, [50, 22, 100]);
+    assert_eq!(scaled, [22, 113, 45]);
+}
+
+#[test]
+fn string_values_refer_to_constants_by_name() {
+    let site = ["north", "nord", "south"].map(|site| Some(site.to_owned()));
+    let ((site,),) = sanitise!(
+        r#"
+            constants:
+              north:
+                type: string
+                value: nord
+            processes:
+              - name: constants
+                columns:
+                  - title: site
+                    column-type: string
+                    invalid-values: [north]
+                    on-invalid: sentinel
+                    invalid-sentinel: unknown
+        "#,
+        (&site,),
+    )
+    .unwrap();
+
+    // 'north' is the name of a constant, so it is replaced by its value, but 'unknown' is not.
+    assert_eq!(site, ["north", "unknown", "south"]);
+}
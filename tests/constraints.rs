@@ -0,0 +1,95 @@
+//! Checks how row constraints are applied.
+
+use sanitise::sanitise;
+
+#[test]
+fn violations_abort() {
+    let start = [1, 5, 3].map(Some);
+    let end = [2, 4, 1].map(Some);
+    let result = sanitise!(
+        r#"
+            processes:
+              - name: constraints
+                row-constraints:
+                  - constraint: value_end >= value_start
+                columns:
+                  - title: start
+                    column-type: integer
+                  - title: end
+                    column-type: integer
+        "#,
+        (&start, &end),
+    );
+
+    assert_eq!(
+        result,
+        Err((
+            "row constraint 'value_end >= value_start' violated".to_owned(),
+            2
+        ))
+    );
+}
+
+#[test]
+fn violations_are_checked_before_columns() {
+    let start = [1, 5, 3].map(Some);
+    let end = [2, 4, 100].map(Some);
+    let result = sanitise!(
+        r#"
+            processes:
+              - name: constraints
+                row-constraints:
+                  - constraint: value_end >= value_start
+                columns:
+                  - title: start
+                    column-type: integer
+                    max: 4
+                  - title: end
+                    column-type: integer
+        "#,
+        (&start, &end),
+    );
+
+    // Row 2 fails both the constraint and 'max', but the constraint is checked first.
+    assert_eq!(
+        result,
+        Err((
+            "row constraint 'value_end >= value_start' violated".to_owned(),
+            2
+        ))
+    );
+}
+
+#[test]
+fn violations_delete() {
+    let start = [1, 5, 3, 2].map(Some);
+    let end = [2, 4, 3, 9].map(Some);
+    let ((start, end),) = sanitise!(
+        r#"
+            processes:
+              - name: constraints
+                row-constraints:
+                  - constraint: value_end >= value_start
+                    on-violation: delete
+                  - constraint: value_end - value_start < 5
+                    on-violation: delete
+                columns:
+                  - title: start
+                    column-type: integer
+                    max: 4
+                  - title: end
+                    column-type: integer
+                    output: value - prev(value)
+                    on-error: invalid
+                    on-invalid: sentinel
+                    invalid-sentinel: 0
+        "#,
+        (&start, &end),
+    )
+    .unwrap();
+
+    // The deleted rows are never passed to the columns, so 'max' does not abort, but 'prev' still
+    // sees the raw values of deleted rows.
+    assert_eq!(start, [1, 3]);
+    assert_eq!(end, [0, -1]);
+}
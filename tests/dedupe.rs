@@ -0,0 +1,141 @@
+//! Checks how duplicate rows are removed.
+
+use sanitise::sanitise;
+
+#[test]
+fn keep_last() {
+    let ids = [1, 2, 1, 3, 1].map(Some);
+    let values = [10, 20, 30, 40, 50].map(Some);
+    let ((ids, values),) = sanitise!(
+        r#"
+            processes:
+              - name: dedupe
+                dedupe: [id]
+                on-duplicate: keep-last
+                columns:
+                  - title: id
+                    column-type: integer
+                  - title: value
+                    column-type: integer
+        "#,
+        (&ids, &values),
+    )
+    .unwrap();
+
+    assert_eq!(ids, [2, 3, 1]);
+    assert_eq!(values, [20, 40, 50]);
+}
+
+#[test]
+fn keep_last_ignores_deleted_rows() {
+    let ids = [1, 2, 1, 3].map(Some);
+    let values = [10, 20, 99, 40].map(Some);
+    let ((ids, values),) = sanitise!(
+        r#"
+            processes:
+              - name: dedupe
+                dedupe: [id]
+                on-duplicate: keep-last
+                columns:
+                  - title: id
+                    column-type: integer
+                  - title: value
+                    column-type: integer
+                    max: 50
+                    on-invalid: delete
+        "#,
+        (&ids, &values),
+    )
+    .unwrap();
+
+    // The later duplicate is deleted by 'value', so the earlier row is kept.
+    assert_eq!(ids, [1, 2, 3]);
+    assert_eq!(values, [10, 20, 40]);
+}
+
+#[test]
+fn unique_columns_abort() {
+    let ids = [Some(1), None, Some(2), None, Some(1)];
+    let result = sanitise!(
+        r#"
+            processes:
+              - name: dedupe
+                columns:
+                  - title: id
+                    column-type: integer
+                    unique: true
+                    on-null: sentinel
+                    null-sentinel: 0
+        "#,
+        (&ids,),
+    );
+
+    // Null entries are never duplicates.
+    assert_eq!(
+        result,
+        Err(("duplicate value in column 'id'".to_owned(), 5))
+    );
+}
+
+#[test]
+fn duplicates_are_found_after_row_constraints_and_before_columns() {
+    let ids = [1, 2, 1, 1, 2].map(Some);
+    let values = [-1, 20, 30, 40, -50].map(Some);
+    let ((ids, values),) = sanitise!(
+        r#"
+            processes:
+              - name: dedupe
+                row-constraints:
+                  - constraint: value_value > 0
+                    on-violation: delete
+                dedupe: [id]
+                on-duplicate: delete-later
+                columns:
+                  - title: id
+                    column-type: integer
+                  - title: value
+                    column-type: integer
+                    max: 35
+        "#,
+        (&ids, &values),
+    )
+    .unwrap();
+
+    // The first row is deleted by the constraint, so the third row is the first with its id. The
+    // fourth row is deleted as a duplicate before 'max' can abort.
+    assert_eq!(ids, [2, 1]);
+    assert_eq!(values, [20, 30]);
+}
+
+#[test]
+fn null_surrogates_are_not_duplicates() {
+    let ids = [-1, 2, -1, 3].map(Some);
+    let codes = [None, Some(-1), Some(5), Some(5)];
+    let ((ids, codes),) = sanitise!(
+        r#"
+            processes:
+              - name: dedupe
+                dedupe: [code]
+                on-duplicate: delete-later
+                columns:
+                  - title: id
+                    column-type: integer
+                    unique: true
+                    null-surrogates: [-1]
+                    on-null: sentinel
+                    null-sentinel: 0
+                  - title: code
+                    column-type: integer
+                    null-surrogates: [-1]
+                    on-null: sentinel
+                    null-sentinel: 0
+        "#,
+        (&ids, &codes),
+    )
+    .unwrap();
+
+    // Surrogates in a unique column are skipped like null entries, and in a row key they are equal
+    // to a null entry.
+    assert_eq!(ids, [0, 0]);
+    assert_eq!(codes, [0, 5]);
+}
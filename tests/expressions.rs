@@ -0,0 +1,478 @@
+//! Checks how output expressions are evaluated.
+
+use sanitise::sanitise;
+
+#[test]
+fn integers_are_promoted_to_reals() {
+    let a = [1, 2, 3].map(Some);
+    let b = [1.5, 2.0, 2.5].map(Some);
+    let ((scaled, greater),) = sanitise!(
+        r#"
+            processes:
+              - name: promotion
+                columns:
+                  - title: a
+                    column-type: integer
+                    output-type: real
+                    output: value * 1.5
+                  - title: b
+                    column-type: real
+                    output-type: boolean
+                    output: value > 2
+        "#,
+        (&a, &b),
+    )
+    .unwrap();
+
+    assert_eq!(scaled, [1.5, 3.0, 4.5]);
+    assert_eq!(greater, [false, false, true]);
+}
+
+#[test]
+fn bounds_of_the_other_numeric_type() {
+    let a = [1, 2, 3].map(Some);
+    let b = [0.5, 1.0, 1.5].map(Some);
+    let ((a, b),) = sanitise!(
+        r#"
+            processes:
+              - name: bounds
+                columns:
+                  - title: a
+                    column-type: integer
+                    max: 2.5
+                    on-invalid: sentinel
+                    invalid-sentinel: 0
+                  - title: b
+                    column-type: real
+                    max: 1
+                    on-invalid: sentinel
+                    invalid-sentinel: 0.0
+        "#,
+        (&a, &b),
+    )
+    .unwrap();
+
+    assert_eq!(a, [1, 2, 0]);
+    assert_eq!(b, [0.5, 1.0, 0.0]);
+}
+
+#[test]
+fn failed_expressions_abort() {
+    let a = [Some(1), Some(0)];
+    let result = sanitise!(
+        r#"
+            processes:
+              - name: checked
+                columns:
+                  - title: a
+                    column-type: integer
+                    output: 100 / value
+        "#,
+        (&a,),
+    );
+
+    assert_eq!(
+        result,
+        Err((
+            "error in output of column 'a': division by zero in 100 / 0".to_owned(),
+            2
+        ))
+    );
+}
+
+#[test]
+fn failed_expressions_are_invalid() {
+    let a = [Some(1), Some(i64::MAX), Some(0), Some(-1)];
+    let ((a,),) = sanitise!(
+        r#"
+            processes:
+              - name: checked
+                columns:
+                  - title: a
+                    column-type: integer
+                    output: 100 / value + value * 2
+                    on-error: invalid
+                    on-invalid: sentinel
+                    invalid-sentinel: 0
+        "#,
+        (&a,),
+    )
+    .unwrap();
+
+    assert_eq!(a, [202, 0, 0, -202]);
+}
+
+#[test]
+fn averages_do_not_overflow() {
+    let group = [1, 1, 2, 2, 3].map(Some);
+    let a = [i64::MAX, i64::MAX, i64::MIN, i64::MIN + 2, 0].map(Some);
+    let ((_, a),) = sanitise!(
+        r#"
+            processes:
+              - name: aggregate
+                aggregate-column: group
+                columns:
+                  - title: group
+                    column-type: integer
+                  - title: a
+                    column-type: integer
+                    aggregate: average
+        "#,
+        (&group, &a),
+    )
+    .unwrap();
+
+    assert_eq!(a[..2], [i64::MAX, i64::MIN + 1]);
+}
+
+#[test]
+fn neighbours_at_the_edges_of_the_file() {
+    let a = [Some(1), Some(2), None, Some(4)];
+    let b = [0, 0, 0, 0].map(Some);
+    let ((previous, next),) = sanitise!(
+        r#"
+            processes:
+              - name: neighbours
+                columns:
+                  - title: a
+                    column-type: integer
+                    on-null: sentinel
+                    null-sentinel: 0
+                    output: prev(value)
+                    on-error: invalid
+                    on-invalid: sentinel
+                    invalid-sentinel: -1
+                  - title: b
+                    column-type: integer
+                    output: next(value_a)
+                    on-error: invalid
+                    on-invalid: sentinel
+                    invalid-sentinel: -1
+        "#,
+        (&a, &b),
+    )
+    .unwrap();
+
+    // The null entry uses its sentinel, so its own output is not evaluated.
+    assert_eq!(previous, [-1, 1, 0, -1]);
+    assert_eq!(next, [2, -1, 4, -1]);
+}
+
+#[test]
+fn row_and_line_after_deletion() {
+    let a = [1, 5, 2, 6].map(Some);
+    let b = [0, 0, 0, 0].map(Some);
+    let ((_, _), (rows, lines)) = sanitise!(
+        r#"
+            processes:
+              - name: delete
+                columns:
+                  - title: a
+                    column-type: integer
+                    max: 2
+                    on-invalid: delete
+                  - title: b
+                    column-type: integer
+              - name: context
+                columns:
+                  - title: a
+                    column-type: integer
+                    output: row
+                  - title: b
+                    column-type: integer
+                    output: line
+        "#,
+        (&a, &b),
+    )
+    .unwrap();
+
+    assert_eq!(rows, [0, 1]);
+    assert_eq!(lines, [1, 3]);
+}
+
+#[test]
+fn raw_and_output_references() {
+    let a = [1, 2, 3].map(Some);
+    let b = [Some(10), None, Some(30)];
+    let ((_, b, c),) = sanitise!(
+        r#"
+            processes:
+              - name: references
+                columns:
+                  - title: a
+                    column-type: integer
+                    output: value * 2
+                  - title: b
+                    column-type: integer
+                    on-null: sentinel
+                    null-sentinel: 0
+                    output: value + out_a
+                  - title: c
+                    column-type: integer
+                    output: raw_b - raw_a
+                    on-error: invalid
+                    on-invalid: sentinel
+                    invalid-sentinel: 0
+        "#,
+        (&a, &b, &a),
+    )
+    .unwrap();
+
+    assert_eq!(b, [12, 0, 36]);
+    assert_eq!(c, [9, 0, 27]);
+}
+
+#[test]
+fn null_handling_functions() {
+    let a = [Some(1), None, Some(3)];
+    let b = [Some(10), Some(20), None];
+    let c = [0, 0, 0].map(Some);
+    let ((_, coalesced, defaults, nulls),) = sanitise!(
+        r#"
+            processes:
+              - name: nulls
+                columns:
+                  - title: a
+                    column-type: integer
+                    on-null: sentinel
+                    null-sentinel: 0
+                  - title: b
+                    column-type: integer
+                    on-null: sentinel
+                    null-sentinel: 0
+                    output: coalesce(raw_a, value)
+                  - title: c
+                    column-type: integer
+                    output: default(raw_b, 42)
+                  - title: d
+                    column-type: integer
+                    output-type: boolean
+                    output: is_null(next(raw_a))
+        "#,
+        (&a, &b, &c, &c),
+    )
+    .unwrap();
+
+    assert_eq!(coalesced, [1, 20, 0]);
+    assert_eq!(defaults, [10, 20, 42]);
+    assert_eq!(nulls, [true, false, true]);
+}
+
+#[test]
+fn integer_defaults_are_promoted() {
+    let a = [Some(1.5), None];
+    let b = [Some("x".to_owned()), None];
+    let c = [0.0, 0.0].map(Some);
+    let ((_, _, reals, strings),) = sanitise!(
+        r#"
+            processes:
+              - name: nulls
+                columns:
+                  - title: a
+                    column-type: real
+                    on-null: sentinel
+                    null-sentinel: 0
+                  - title: b
+                    column-type: string
+                    on-null: sentinel
+                    null-sentinel: ""
+                  - title: c
+                    column-type: real
+                    output: default(raw_a, 0)
+                  - title: d
+                    column-type: real
+                    output-type: string
+                    output: default(raw_b, "none")
+        "#,
+        (&a, &b, &c, &c),
+    )
+    .unwrap();
+
+    assert_eq!(reals, [1.5, 0.0]);
+    assert_eq!(strings, ["x", "none"]);
+}
+
+#[test]
+fn process_variables() {
+    let a = [1, 2].map(Some);
+    let b = [10, 20].map(Some);
+    let ((a, b),) = sanitise!(
+        r#"
+            processes:
+              - name: variables
+                variables:
+                  total: raw_a + raw_b
+                  double: total * 2
+                columns:
+                  - title: a
+                    column-type: integer
+                    output: total
+                  - title: b
+                    column-type: integer
+                    output: double - value
+        "#,
+        (&a, &b),
+    )
+    .unwrap();
+
+    assert_eq!(a, [11, 22]);
+    assert_eq!(b, [12, 24]);
+}
+
+#[test]
+fn failed_variables_abort() {
+    let a = [1, 0].map(Some);
+    let b = [10, 20].map(Some);
+    let result = sanitise!(
+        r#"
+            processes:
+              - name: variables
+                variables:
+                  ratio: raw_b / raw_a
+                columns:
+                  - title: a
+                    column-type: integer
+                    on-error: invalid
+                    on-invalid: sentinel
+                    invalid-sentinel: 0
+                    output: ratio
+                  - title: b
+                    column-type: integer
+        "#,
+        (&a, &b),
+    );
+
+    // Variables are not covered by the columns' 'on-error'.
+    assert_eq!(
+        result,
+        Err((
+            "error in variable 'ratio': division by zero in 20 / 0".to_owned(),
+            2
+        ))
+    );
+}
+
+fn scale(value: f64, factor: f64) -> f64 {
+    value * factor
+}
+
+fn label(value: i64) -> String {
+    format!("#{value}")
+}
+
+#[test]
+fn user_functions() {
+    let a = [1, 2].map(Some);
+    let ((scaled, labels),) = sanitise!(
+        r#"
+            functions:
+              scale:
+                path: "crate::scale"
+                args: [real, real]
+                returns: real
+              label:
+                path: "crate::label"
+                args: [integer]
+                returns: string
+            processes:
+              - name: functions
+                columns:
+                  - title: a
+                    column-type: integer
+                    output-type: real
+                    output: scale(value, 2)
+                  - title: b
+                    column-type: integer
+                    output-type: string
+                    output: label(value)
+        "#,
+        (&a, &a),
+    )
+    .unwrap();
+
+    assert_eq!(scaled, [2.0, 4.0]);
+    assert_eq!(labels, ["#1", "#2"]);
+}
+
+#[test]
+fn format_strings() {
+    let a = [7, 255].map(Some);
+    let b = [0.5, 12.25].map(Some);
+    let ((formatted, _),) = sanitise!(
+        r#"
+            processes:
+              - name: format
+                columns:
+                  - title: a
+                    column-type: integer
+                    output-type: string
+                    output: format("{:03}/{:x}/{:>6.2}", value, value, raw_b)
+                  - title: b
+                    column-type: real
+        "#,
+        (&a, &b),
+    )
+    .unwrap();
+
+    assert_eq!(formatted, ["007/7/  0.50", "255/ff/ 12.25"]);
+}
+
+#[test]
+fn valid_if() {
+    let pulse = [60, 300, 150, 0].map(Some);
+    let age = [20, 20, 80, 0].map(Some);
+    let ((pulse, age),) = sanitise!(
+        r#"
+            processes:
+              - name: validity
+                columns:
+                  - title: pulse
+                    column-type: integer
+                    max: 250
+                    valid-if: value < 220 - raw_age
+                    on-invalid: sentinel
+                    invalid-sentinel: -1
+                  - title: age
+                    column-type: integer
+                    valid-if: 100 / value > 1
+                    on-error: invalid
+                    on-invalid: sentinel
+                    invalid-sentinel: -1
+        "#,
+        (&pulse, &age),
+    )
+    .unwrap();
+
+    assert_eq!(pulse, [60, -1, -1, 0]);
+    assert_eq!(age, [20, 20, -1, -1]);
+}
+
+#[test]
+fn null_surrogates_are_null_in_expressions() {
+    let a = [Some(1), Some(-999), None, Some(-999)];
+    let b = [0, 0, 0, 0].map(Some);
+    let ((_, defaults, nulls),) = sanitise!(
+        r#"
+            processes:
+              - name: expressions
+                columns:
+                  - title: a
+                    column-type: integer
+                    null-surrogates: [-999]
+                    on-null: sentinel
+                    null-sentinel: 0
+                  - title: b
+                    column-type: integer
+                    output: default(raw_a, 42)
+                  - title: c
+                    column-type: integer
+                    output-type: boolean
+                    output: is_null(prev(value_a))
+        "#,
+        (&a, &b, &b),
+    )
+    .unwrap();
+
+    assert_eq!(defaults, [1, 42, 42, 42]);
+    assert_eq!(nulls, [true, false, true, true]);
+}
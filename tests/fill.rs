@@ -0,0 +1,190 @@
+//! Checks the options that fill in missing values using the values around them.
+
+use sanitise::sanitise;
+
+#[test]
+fn interpolate() {
+    let values = [Some(0), Some(99), None, Some(99), Some(10), Some(20)];
+    let ((values,),) = sanitise!(
+        r#"
+            processes:
+              - name: fill
+                columns:
+                  - title: value
+                    column-type: integer
+                    max: 50
+                    on-invalid: interpolate
+                    on-null: interpolate
+                    valid-streak: 2
+        "#,
+        (&values,),
+    )
+    .unwrap();
+
+    // 2.5, 5 and 7.5 are rounded.
+    assert_eq!(values, [0, 3, 5, 8, 10, 20]);
+}
+
+#[test]
+fn interpolate_by() {
+    let times = [Some(0.0), Some(1.0), None, Some(9.0), Some(10.0)];
+    let values = [Some(0.0), Some(99.0), Some(99.0), Some(99.0), Some(10.0)];
+    let ((_, values),) = sanitise!(
+        r#"
+            processes:
+              - name: fill
+                columns:
+                  - title: time
+                    column-type: real
+                    on-null: sentinel
+                    null-sentinel: 0.0
+                  - title: value
+                    column-type: real
+                    max: 50
+                    on-invalid: interpolate
+                    valid-streak: 1
+                    interpolate-by: time
+        "#,
+        (&times, &values),
+    )
+    .unwrap();
+
+    // The row without a time is spaced by its row number.
+    assert_eq!(values, [0.0, 1.0, 5.0, 9.0, 10.0]);
+}
+
+#[test]
+fn next_and_nearest() {
+    let values = [
+        None,
+        Some(10),
+        Some(99),
+        Some(99),
+        Some(99),
+        Some(20),
+        Some(99),
+    ];
+    let ((next, nearest),) = sanitise!(
+        r#"
+            processes:
+              - name: fill
+                columns:
+                  - title: next
+                    column-type: integer
+                    max: 50
+                    on-invalid: next
+                    on-null: next
+                  - title: nearest
+                    column-type: integer
+                    max: 50
+                    on-invalid: nearest
+                    on-null: nearest
+        "#,
+        (&values, &values),
+    )
+    .unwrap();
+
+    // The series at the end has no value after it, so it uses the one before it.
+    assert_eq!(next, [10, 10, 20, 20, 20, 20, 20]);
+    // The middle of an odd series is halfway between the values, so it uses the one before it.
+    assert_eq!(nearest, [10, 10, 10, 10, 20, 20, 20]);
+}
+
+#[test]
+fn nearest_by() {
+    let times = [0, 1, 8, 10].map(Some);
+    let values = [0, 99, 99, 10].map(Some);
+    let ((_, values),) = sanitise!(
+        r#"
+            processes:
+              - name: fill
+                columns:
+                  - title: time
+                    column-type: integer
+                  - title: value
+                    column-type: integer
+                    max: 50
+                    on-invalid: nearest
+                    nearest-by: time
+        "#,
+        (&times, &values),
+    )
+    .unwrap();
+
+    assert_eq!(values, [0, 0, 10, 10]);
+}
+
+#[test]
+fn interpolate_by_skips_deleted_rows() {
+    let times = [0, 1, 2, 3, 10].map(Some);
+    let values = [Some(0.0), Some(99.0), Some(99.0), None, Some(10.0)];
+    let ((_, values),) = sanitise!(
+        r#"
+            processes:
+              - name: fill
+                columns:
+                  - title: time
+                    column-type: integer
+                  - title: value
+                    column-type: real
+                    max: 50
+                    on-invalid: delete
+                    on-null: interpolate
+                    valid-streak: 1
+                    interpolate-by: time
+        "#,
+        (&times, &values),
+    )
+    .unwrap();
+
+    assert_eq!(values, [0.0, 3.0, 10.0]);
+}
+
+#[test]
+fn nearest_by_skips_deleted_rows() {
+    let times = [0, 2, 3, 4, 10].map(Some);
+    let values = [Some(0), Some(99), Some(99), None, Some(10)];
+    let ((_, values),) = sanitise!(
+        r#"
+            processes:
+              - name: fill
+                columns:
+                  - title: time
+                    column-type: integer
+                  - title: value
+                    column-type: integer
+                    max: 50
+                    on-invalid: delete
+                    on-null: nearest
+                    nearest-by: time
+        "#,
+        (&times, &values),
+    )
+    .unwrap();
+
+    assert_eq!(values, [0, 0, 10]);
+}
+
+#[test]
+fn clamped_values_end_a_series() {
+    let values = [10, 7, 99, 20].map(Some);
+    let ((values,),) = sanitise!(
+        r#"
+            processes:
+              - name: fill
+                columns:
+                  - title: value
+                    column-type: integer
+                    max: 50
+                    invalid-values: [7]
+                    on-invalid: clamp
+                    clamp-fallback: average
+                    valid-streak: 1
+        "#,
+        (&values,),
+    )
+    .unwrap();
+
+    // The invalid 7 is averaged with the value that 99 was clamped to.
+    assert_eq!(values, [10, 30, 50, 20]);
+}
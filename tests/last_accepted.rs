@@ -0,0 +1,110 @@
+//! Checks the options that compare each value with the last value that was accepted.
+
+use sanitise::sanitise;
+
+#[test]
+fn ties_under_strict_and_non_strict_orders() {
+    let a = [1, 2, 2, 3, 1].map(Some);
+    let ((increasing, strictly_increasing),) = sanitise!(
+        r#"
+            processes:
+              - name: monotonic
+                columns:
+                  - title: increasing
+                    column-type: integer
+                    monotonic: non-decreasing
+                    on-invalid: sentinel
+                    invalid-sentinel: 0
+                  - title: strictly_increasing
+                    column-type: integer
+                    monotonic: strictly-increasing
+                    on-invalid: sentinel
+                    invalid-sentinel: 0
+        "#,
+        (&a, &a),
+    )
+    .unwrap();
+
+    assert_eq!(increasing, [1, 2, 2, 3, 0]);
+    assert_eq!(strictly_increasing, [1, 2, 0, 3, 0]);
+}
+
+#[test]
+fn rejected_values_are_not_accepted() {
+    let a = [Some(5), Some(10), Some(3), None, Some(7), Some(9), Some(8)];
+    let b = [0, 0, 0, 0, 0, 1, 0].map(Some);
+    let ((a, _),) = sanitise!(
+        r#"
+            processes:
+              - name: monotonic
+                columns:
+                  - title: a
+                    column-type: integer
+                    monotonic: increasing
+                    max: 9
+                    on-invalid: sentinel
+                    invalid-sentinel: 0
+                    on-null: sentinel
+                    null-sentinel: 0
+                  - title: b
+                    column-type: integer
+                    max: 0
+                    on-invalid: delete
+        "#,
+        (&a, &b),
+    )
+    .unwrap();
+
+    // 10 is over 'max', 3 is out of order, and the row with 9 is deleted by 'b', so none of them
+    // change the value that 7 and 8 are compared with.
+    assert_eq!(a, [5, 0, 0, 0, 7, 8]);
+}
+
+#[test]
+fn real_max_change_is_rounded_down_for_integers() {
+    let a = [10, 12, 15, 13, 10].map(Some);
+    let ((a,),) = sanitise!(
+        r#"
+            processes:
+              - name: changes
+                columns:
+                  - title: a
+                    column-type: integer
+                    max-change: 2.5
+                    on-invalid: sentinel
+                    invalid-sentinel: 0
+        "#,
+        (&a,),
+    )
+    .unwrap();
+
+    assert_eq!(a, [10, 12, 0, 13, 0]);
+}
+
+#[test]
+fn max_change_ratio_after_zero() {
+    let a = [0.0, 0.5, 0.0, -0.0, 10.0, 0.0].map(Some);
+    let b = [100, 200, 0, 0, 1, 0].map(Some);
+    let ((a, b),) = sanitise!(
+        r#"
+            processes:
+              - name: ratios
+                columns:
+                  - title: a
+                    column-type: real
+                    max-change-ratio: 0.5
+                    on-invalid: sentinel
+                    invalid-sentinel: -1.0
+                  - title: b
+                    column-type: integer
+                    max-change-ratio: 1
+                    on-invalid: sentinel
+                    invalid-sentinel: -1
+        "#,
+        (&a, &b),
+    )
+    .unwrap();
+
+    assert_eq!(a, [0.0, -1.0, 0.0, -0.0, -1.0, 0.0]);
+    assert_eq!(b, [100, 200, 0, 0, -1, 0]);
+}
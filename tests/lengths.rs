@@ -0,0 +1,33 @@
+//! Checks the limits on the length of strings.
+
+use sanitise::sanitise;
+
+#[test]
+fn lengths_in_chars_and_bytes() {
+    let names = ["ab", "abc", "abcdef", "café", "ü"].map(|name| Some(name.to_owned()));
+    let ((chars, bytes),) = sanitise!(
+        r#"
+            processes:
+              - name: lengths
+                columns:
+                  - title: chars
+                    column-type: string
+                    min-length: 2
+                    max-length: 4
+                    on-invalid: sentinel
+                    invalid-sentinel: ""
+                  - title: bytes
+                    column-type: string
+                    min-length: 2
+                    max-length: 4
+                    length-unit: bytes
+                    on-invalid: sentinel
+                    invalid-sentinel: ""
+        "#,
+        (&names, &names),
+    )
+    .unwrap();
+
+    assert_eq!(chars, ["ab", "abc", "", "café", ""]);
+    assert_eq!(bytes, ["ab", "abc", "", "", "ü"]);
+}
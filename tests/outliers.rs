@@ -0,0 +1,78 @@
+//! Checks that outliers are treated as invalid values.
+
+use sanitise::sanitise;
+
+#[test]
+fn zscore() {
+    let values = [10, 12, 100, 8, 10, 11, 9, 10].map(Some);
+    let ((values,),) = sanitise!(
+        r#"
+            processes:
+              - name: outliers
+                columns:
+                  - title: value
+                    column-type: integer
+                    outliers:
+                      method: zscore
+                      threshold: 2
+                    on-invalid: previous
+                    invalid-sentinel: 0
+        "#,
+        (&values,),
+    )
+    .unwrap();
+
+    assert_eq!(values, [10, 12, 12, 8, 10, 11, 9, 10]);
+}
+
+#[test]
+fn outliers_deleted_by_a_later_column() {
+    let values = [10, 12, 100, 8, 10, 11, 9, 10].map(Some);
+    let flags = [1, 1, 0, 1, 1, 1, 1, 1].map(Some);
+    let ((values, _),) = sanitise!(
+        r#"
+            processes:
+              - name: outliers
+                columns:
+                  - title: value
+                    column-type: integer
+                    outliers:
+                      method: iqr
+                    on-invalid: average
+                    valid-streak: 1
+                  - title: flag
+                    column-type: integer
+                    valid-values: [1]
+                    on-invalid: delete
+        "#,
+        (&values, &flags),
+    )
+    .unwrap();
+
+    // The outlier is deleted before the series it starts is filled in, so nothing is averaged.
+    assert_eq!(values, [10, 12, 8, 10, 11, 9, 10]);
+}
+
+#[test]
+fn hampel_uses_nearby_values() {
+    let values = [1.0, 1.1, 0.9, 5.0, 1.0, 20.0, 21.0, 19.0, 20.5].map(Some);
+    let ((values,),) = sanitise!(
+        r#"
+            processes:
+              - name: outliers
+                columns:
+                  - title: value
+                    column-type: real
+                    outliers:
+                      method: hampel
+                      window: 2
+                    on-invalid: sentinel
+                    invalid-sentinel: -1
+        "#,
+        (&values,),
+    )
+    .unwrap();
+
+    // The step up to 20 is not an outlier, as the values around it are similar.
+    assert_eq!(values, [1.0, 1.1, 0.9, -1.0, 1.0, 20.0, 21.0, 19.0, 20.5]);
+}
@@ -0,0 +1,161 @@
+//! Checks that the patterns compiled by the macro agree with the `regex` crate.
+
+use regex::Regex;
+use sanitise::sanitise;
+
+/// The patterns used by the columns below, in the same order.
+const PATTERNS: [&str; 16] = [
+    "",
+    "^$",
+    r"^[A-Z]{2}-\d{6}$",
+    "ab|cd",
+    "^(?:ab|cd)+$",
+    "a{2,3}",
+    "^a{2,3}$",
+    "^a{2,}$",
+    "(?m)^b$",
+    r"^\p{Greek}+$",
+    "colou?r",
+    r"^\w+@\w+\.com$",
+    "^(?:ab|cd|ef)$",
+    "^a{3}$",
+    "^[a-cx]+$",
+    "é",
+];
+
+const TEXTS: [&str; 22] = [
+    "",
+    "a",
+    "aa",
+    "aaa",
+    "aaaa",
+    "AB-123456",
+    "AB-12345",
+    "xx AB-123456",
+    "abcd",
+    "cdab",
+    "abc",
+    "a\nb\nc",
+    "b\n",
+    "λόγος",
+    "color",
+    "colour",
+    "me@site.com",
+    "ünï@straße.com",
+    "ef",
+    "abx",
+    "café",
+    "cafe\u{301}",
+];
+
+#[test]
+#[allow(clippy::too_many_lines)]
+fn patterns_agree_with_regex() {
+    let texts: Vec<Option<String>> = TEXTS.iter().map(|text| Some(text.to_string())).collect();
+    let ((p0, p1, p2, p3, p4, p5, p6, p7, p8, p9, p10, p11, p12, p13, p14, p15),) = sanitise!(
+        r#"
+            processes:
+              - name: patterns
+                columns:
+                  - title: p0
+                    column-type: string
+                    pattern: ''
+                    on-invalid: sentinel
+                    invalid-sentinel: <invalid>
+                  - title: p1
+                    column-type: string
+                    pattern: '^$'
+                    on-invalid: sentinel
+                    invalid-sentinel: <invalid>
+                  - title: p2
+                    column-type: string
+                    pattern: '^[A-Z]{2}-\d{6}$'
+                    on-invalid: sentinel
+                    invalid-sentinel: <invalid>
+                  - title: p3
+                    column-type: string
+                    pattern: 'ab|cd'
+                    on-invalid: sentinel
+                    invalid-sentinel: <invalid>
+                  - title: p4
+                    column-type: string
+                    pattern: '^(?:ab|cd)+$'
+                    on-invalid: sentinel
+                    invalid-sentinel: <invalid>
+                  - title: p5
+                    column-type: string
+                    pattern: 'a{2,3}'
+                    on-invalid: sentinel
+                    invalid-sentinel: <invalid>
+                  - title: p6
+                    column-type: string
+                    pattern: '^a{2,3}$'
+                    on-invalid: sentinel
+                    invalid-sentinel: <invalid>
+                  - title: p7
+                    column-type: string
+                    pattern: '^a{2,}$'
+                    on-invalid: sentinel
+                    invalid-sentinel: <invalid>
+                  - title: p8
+                    column-type: string
+                    pattern: '(?m)^b$'
+                    on-invalid: sentinel
+                    invalid-sentinel: <invalid>
+                  - title: p9
+                    column-type: string
+                    pattern: '^\p{Greek}+$'
+                    on-invalid: sentinel
+                    invalid-sentinel: <invalid>
+                  - title: p10
+                    column-type: string
+                    pattern: 'colou?r'
+                    on-invalid: sentinel
+                    invalid-sentinel: <invalid>
+                  - title: p11
+                    column-type: string
+                    pattern: '^\w+@\w+\.com$'
+                    on-invalid: sentinel
+                    invalid-sentinel: <invalid>
+                  - title: p12
+                    column-type: string
+                    pattern: '^(?:ab|cd|ef)$'
+                    on-invalid: sentinel
+                    invalid-sentinel: <invalid>
+                  - title: p13
+                    column-type: string
+                    pattern: '^a{3}$'
+                    on-invalid: sentinel
+                    invalid-sentinel: <invalid>
+                  - title: p14
+                    column-type: string
+                    pattern: '^[a-cx]+$'
+                    on-invalid: sentinel
+                    invalid-sentinel: <invalid>
+                  - title: p15
+                    column-type: string
+                    pattern: 'é'
+                    on-invalid: sentinel
+                    invalid-sentinel: <invalid>
+        "#,
+        (
+            &texts, &texts, &texts, &texts, &texts, &texts, &texts, &texts, &texts, &texts, &texts,
+            &texts, &texts, &texts, &texts, &texts
+        ),
+    )
+    .unwrap();
+
+    let columns = [
+        p0, p1, p2, p3, p4, p5, p6, p7, p8, p9, p10, p11, p12, p13, p14, p15,
+    ];
+    for (pattern, column) in PATTERNS.iter().zip(columns) {
+        let regex = Regex::new(pattern).unwrap();
+        for (text, output) in TEXTS.iter().zip(column) {
+            assert_eq!(
+                output != "<invalid>",
+                regex.is_match(text),
+                "pattern {pattern:?} on {text:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! Checks that invalid values can be replaced with a statistic of the last valid values.
+
+use sanitise::sanitise;
+
+#[test]
+fn rolling_mean() {
+    let values = [99, 10, 20, 99, 30, 99].map(Some);
+    let ((values,),) = sanitise!(
+        r#"
+            processes:
+              - name: rolling
+                columns:
+                  - title: value
+                    column-type: integer
+                    max: 50
+                    on-invalid: rolling
+                    invalid-sentinel: 0
+                    window: 2
+        "#,
+        (&values,),
+    )
+    .unwrap();
+
+    assert_eq!(values, [0, 10, 20, 15, 30, 25]);
+}
+
+#[test]
+fn rolling_median() {
+    let values = [Some(1.0), Some(5.0), Some(3.0), None, Some(4.0), None];
+    let ((values,),) = sanitise!(
+        r#"
+            processes:
+              - name: rolling
+                columns:
+                  - title: value
+                    column-type: real
+                    on-null: rolling
+                    null-sentinel: 0
+                    window: 3
+                    statistic: median
+        "#,
+        (&values,),
+    )
+    .unwrap();
+
+    assert_eq!(values, [1.0, 5.0, 3.0, 3.0, 4.0, 4.0]);
+}
+
+#[test]
+fn rolling_ignores_deleted_rows() {
+    let values = [10, 20, 40, 99].map(Some);
+    let flags = [1, 1, 0, 1].map(Some);
+    let ((values, _),) = sanitise!(
+        r#"
+            processes:
+              - name: rolling
+                columns:
+                  - title: value
+                    column-type: integer
+                    max: 50
+                    on-invalid: rolling
+                    invalid-sentinel: 0
+                    window: 2
+                  - title: flag
+                    column-type: integer
+                    valid-values: [1]
+                    on-invalid: delete
+        "#,
+        (&values, &flags),
+    )
+    .unwrap();
+
+    // 40 is deleted by 'flag', so 10 is used again.
+    assert_eq!(values, [10, 20, 15]);
+}
@@ -0,0 +1,151 @@
+//! Checks how long series of invalid values and null entries are handled.
+
+use sanitise::sanitise;
+
+#[test]
+fn long_series_abort() {
+    let values = [Some(1), None, None, Some(2)];
+    let result = sanitise!(
+        r#"
+            processes:
+              - name: streaks
+                columns:
+                  - title: value
+                    column-type: integer
+                    on-null: previous
+                    null-sentinel: 0
+                    max-null-streak: 1
+        "#,
+        (&values,),
+    );
+
+    assert_eq!(
+        result,
+        Err((
+            "more than 1 consecutive null entries in column 'value'".to_owned(),
+            3
+        ))
+    );
+}
+
+#[test]
+fn long_series_are_deleted() {
+    let values = [1, 100, 2, 100, 100, 100, 3].map(Some);
+    let ((values,),) = sanitise!(
+        r#"
+            processes:
+              - name: streaks
+                columns:
+                  - title: value
+                    column-type: integer
+                    max: 50
+                    on-invalid: previous
+                    invalid-sentinel: 0
+                    max-invalid-streak: 2
+                    on-long-streak: delete
+        "#,
+        (&values,),
+    )
+    .unwrap();
+
+    assert_eq!(values, [1, 1, 2, 3]);
+}
+
+#[test]
+fn deleted_values_are_not_counted() {
+    let values = [1, 2, 3, 100, 100, 100, 4].map(Some);
+    let ((values,),) = sanitise!(
+        r#"
+            processes:
+              - name: streaks
+                columns:
+                  - title: value
+                    column-type: integer
+                    max: 50
+                    on-invalid: delete
+                    max-invalid-streak: 2
+                    on-long-streak: delete
+        "#,
+        (&values,),
+    )
+    .unwrap();
+
+    assert_eq!(values, [1, 2, 3, 4]);
+}
+
+#[test]
+fn deleted_nulls_are_not_counted() {
+    let values = [None, None, None, Some(1), None, Some(2)];
+    let ((values,),) = sanitise!(
+        r#"
+            processes:
+              - name: streaks
+                columns:
+                  - title: value
+                    column-type: integer
+                    on-null: delete
+                    max-null-streak: 2
+                    on-long-streak: delete
+        "#,
+        (&values,),
+    )
+    .unwrap();
+
+    assert_eq!(values, [1, 2]);
+}
+
+#[test]
+fn removed_series_are_not_duplicates() {
+    let ids = [1, 2, 1, 3, 4].map(Some);
+    let values = [5, 5, 100, 100, 5].map(Some);
+    let ((ids, values),) = sanitise!(
+        r#"
+            processes:
+              - name: streaks
+                dedupe: [id]
+                on-duplicate: keep-last
+                columns:
+                  - title: id
+                    column-type: integer
+                  - title: value
+                    column-type: integer
+                    max: 50
+                    on-invalid: previous
+                    invalid-sentinel: 0
+                    max-invalid-streak: 1
+                    on-long-streak: delete
+        "#,
+        (&ids, &values),
+    )
+    .unwrap();
+
+    // The third row replaced the first, so the first is kept again when the series removes it.
+    assert_eq!(ids, [1, 2, 4]);
+    assert_eq!(values, [5, 5, 5]);
+
+    let ids = [1, 2, 3, 2].map(Some);
+    let values = [5, 100, 100, 5].map(Some);
+    let ((ids, values),) = sanitise!(
+        r#"
+            processes:
+              - name: streaks
+                dedupe: [id]
+                on-duplicate: delete-later
+                columns:
+                  - title: id
+                    column-type: integer
+                  - title: value
+                    column-type: integer
+                    max: 50
+                    on-invalid: previous
+                    invalid-sentinel: 0
+                    max-invalid-streak: 1
+                    on-long-streak: delete
+        "#,
+        (&ids, &values),
+    )
+    .unwrap();
+
+    assert_eq!(ids, [1, 2]);
+    assert_eq!(values, [5, 5]);
+}
@@ -0,0 +1,42 @@
+//! Checks how values given in the configuration are converted to the type of a column.
+
+use sanitise::sanitise;
+
+#[test]
+fn scalars_are_coerced() {
+    let count = [5, 15, 25].map(Some);
+    let code = ["-999", "007", "7"].map(|code| Some(code.to_owned()));
+    let flag = [true, false, true].map(Some);
+    let ((count, code, flag),) = sanitise!(
+        r#"
+            processes:
+              - name: values
+                columns:
+                  - title: count
+                    column-type: integer
+                    max: "20"
+                    on-invalid: sentinel
+                    invalid-sentinel: "2 * 10"
+                  - title: code
+                    column-type: string
+                    null-surrogates: [-999]
+                    on-null: sentinel
+                    null-sentinel: 0
+                    invalid-values: [7]
+                    on-invalid: sentinel
+                    invalid-sentinel: true
+                  - title: flag
+                    column-type: boolean
+                    valid-values: [true]
+                    on-invalid: sentinel
+                    invalid-sentinel: true
+                    min: ~
+        "#,
+        (&count, &code, &flag),
+    )
+    .unwrap();
+
+    assert_eq!(count, [5, 15, 20]);
+    assert_eq!(code, ["0", "007", "true"]);
+    assert_eq!(flag, [true, true, true]);
+}